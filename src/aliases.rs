@@ -0,0 +1,168 @@
+//! Publishing the newest drivers under aliases, like `latest.json` and `majors/<major>.json`.
+
+use std::{collections::BTreeMap, fs::create_dir, path::Path};
+
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::{platform::Platform, version::Version, write_json, Output, Properties};
+
+/// A single driver, as published in `latest/<platform>.json`.
+#[derive(Debug, Serialize)]
+pub struct Driver<'a> {
+    /// The version of the driver.
+    pub version: Version,
+    /// The platform the driver is for.
+    pub platform: &'a Platform,
+    /// The properties of the driver, flattened into the same object.
+    #[serde(flatten)]
+    pub properties: &'a Properties,
+}
+
+/// The newest version, as published in `latest.json`.
+#[derive(Debug, Serialize)]
+pub struct Latest<'a> {
+    /// The newest version that still has a driver available upstream.
+    pub version: Version,
+    /// The drivers of `version` that are still available upstream.
+    pub platforms: BTreeMap<&'a Platform, &'a Properties>,
+}
+
+/// Every version of a major release, as published in `majors/<major>.json`.
+#[derive(Debug, Serialize)]
+pub struct Major<'a> {
+    /// The major release, e.g. `118`.
+    pub major: u32,
+    /// The newest version of this major with a driver that wasn't removed upstream, if any.
+    pub latest: Option<Version>,
+    /// The newest driver of this major for each platform, which isn't necessarily `latest`.
+    pub platforms: BTreeMap<&'a Platform, Driver<'a>>,
+    /// Every version of this major, newest first.
+    pub versions: Vec<Version>,
+}
+
+type Entry<'a> = (&'a Version, &'a BTreeMap<Platform, Properties>);
+
+/// Write `latest.json`, `latest/<platform>.json` and `majors/<major>.json` into `out`.
+///
+/// Drivers that were removed upstream stay in the version lists, but are never picked as the
+/// latest one since their download is gone.
+pub fn write_aliases(out: &Path, output: &Output) -> Result<()> {
+    let newest_first: Vec<Entry> = output.0.iter().rev().collect();
+
+    let latest = newest_first.iter().find_map(|(version, platforms)| {
+        let platforms: BTreeMap<_, _> = platforms
+            .iter()
+            .filter(|(_, properties)| is_available(properties))
+            .collect();
+
+        (!platforms.is_empty()).then_some(Latest {
+            version: **version,
+            platforms,
+        })
+    });
+    if let Some(latest) = latest {
+        write_json(&out.join("latest.json"), &latest)?;
+    }
+
+    let latest_dir = out.join("latest");
+    create_dir(&latest_dir)?;
+    for (platform, driver) in newest_per_platform(&newest_first) {
+        write_json(&latest_dir.join(format!("{}.json", platform)), &driver)?;
+    }
+
+    let majors_dir = out.join("majors");
+    create_dir(&majors_dir)?;
+    let mut majors: BTreeMap<u32, Vec<Entry>> = BTreeMap::new();
+    for entry in newest_first {
+        majors.entry(entry.0.major).or_default().push(entry);
+    }
+    for (major, entries) in majors {
+        let content = Major {
+            major,
+            latest: entries
+                .iter()
+                .find(|(_, platforms)| platforms.values().any(is_available))
+                .map(|(version, _)| **version),
+            platforms: newest_per_platform(&entries),
+            versions: entries.iter().map(|(version, _)| **version).collect(),
+        };
+        write_json(&majors_dir.join(format!("{}.json", major)), &content)?;
+    }
+
+    Ok(())
+}
+
+/// The newest available driver for each platform, from entries sorted newest first.
+fn newest_per_platform<'a>(newest_first: &[Entry<'a>]) -> BTreeMap<&'a Platform, Driver<'a>> {
+    let mut drivers = BTreeMap::new();
+    for (version, platforms) in newest_first {
+        for (platform, properties) in platforms.iter() {
+            if is_available(properties) {
+                drivers.entry(platform).or_insert(Driver {
+                    version: **version,
+                    platform,
+                    properties,
+                });
+            }
+        }
+    }
+
+    drivers
+}
+
+fn is_available(properties: &Properties) -> bool {
+    properties.removed_upstream_at.is_none()
+}
+
+#[test]
+fn aliases_pick_the_newest_available_driver() {
+    use crate::fixture::driver;
+
+    let tmp = tempfile::tempdir().unwrap();
+    let output = Output(BTreeMap::from([
+        (
+            "118.0.2088.80".parse().unwrap(),
+            BTreeMap::from([(Platform::Win64, driver("118.80-win64", true))]),
+        ),
+        (
+            "118.0.2088.76".parse().unwrap(),
+            BTreeMap::from([(Platform::Win64, driver("118.76-win64", false))]),
+        ),
+        (
+            "118.0.2088.69".parse().unwrap(),
+            BTreeMap::from([
+                (Platform::Win64, driver("118.69-win64", false)),
+                (Platform::Linux64, driver("118.69-linux64", false)),
+            ]),
+        ),
+        (
+            "119.0.2151.0".parse().unwrap(),
+            BTreeMap::from([(Platform::Linux64, driver("119.0-linux64", true))]),
+        ),
+    ]));
+
+    write_aliases(tmp.path(), &output).unwrap();
+    let read = |path: &str| crate::read_json(&tmp.path().join(path));
+
+    let latest = read("latest.json");
+    assert_eq!(latest["version"], "118.0.2088.76");
+    assert_eq!(latest["platforms"]["win64"]["url"], "118.76-win64");
+
+    let linux = read("latest/linux64.json");
+    assert_eq!(linux["version"], "118.0.2088.69");
+    assert_eq!(linux["url"], "118.69-linux64");
+
+    let major = read("majors/118.json");
+    assert_eq!(major["latest"], "118.0.2088.76");
+    assert_eq!(major["platforms"]["linux64"]["version"], "118.0.2088.69");
+    assert_eq!(
+        major["versions"],
+        serde_json::json!(["118.0.2088.80", "118.0.2088.76", "118.0.2088.69"])
+    );
+
+    let major = read("majors/119.json");
+    assert_eq!(major["latest"], serde_json::Value::Null);
+    assert_eq!(major["platforms"], serde_json::json!({}));
+    assert_eq!(major["versions"], serde_json::json!(["119.0.2151.0"]));
+}
@@ -0,0 +1,107 @@
+//! Detecting the installed Microsoft Edge and picking the driver that is compatible with it.
+
+use std::process::Command;
+
+use anyhow::{anyhow, bail, ensure, Context, Result};
+
+use crate::version::Version;
+
+/// The command Microsoft Edge is installed as on Linux.
+pub const DEFAULT_COMMAND: &str = "microsoft-edge";
+
+/// Parse the browser version out of `microsoft-edge --version` output (e.g.
+/// `Microsoft Edge 118.0.2088.76 unknown`), or out of a bare version string.
+pub fn parse_browser_version(output: &str) -> Result<Version> {
+    output
+        .split_whitespace()
+        .find_map(|word| word.parse().ok())
+        .ok_or_else(|| anyhow!("no browser version in {:?}", output.trim()))
+}
+
+/// Run `<command> --version` to find out which version of Microsoft Edge is installed.
+pub fn installed_version(command: &str) -> Result<Version> {
+    let output = Command::new(command)
+        .arg("--version")
+        .output()
+        .with_context(|| format!("failed to run {} --version", command))?;
+    ensure!(
+        output.status.success(),
+        "{} --version failed with {}: {}",
+        command,
+        output.status,
+        String::from_utf8_lossy(&output.stderr).trim()
+    );
+
+    parse_browser_version(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Pick the driver for `browser` out of `available`: the exact build if there is one, otherwise
+/// the nearest one of the same major release, preferring older builds over newer ones.
+///
+/// Drivers only promise to work with browsers of the same major release, so there is no fallback
+/// to another one.
+pub fn select_compatible(
+    browser: Version,
+    available: impl IntoIterator<Item = Version>,
+) -> Result<Version> {
+    let candidates: Vec<_> = available
+        .into_iter()
+        .filter(|version| version.major == browser.major)
+        .collect();
+
+    if candidates.contains(&browser) {
+        return Ok(browser);
+    }
+
+    let older = candidates.iter().filter(|v| **v < browser).max();
+    let newer = candidates.iter().filter(|v| **v > browser).min();
+    match older.or(newer) {
+        Some(version) => Ok(*version),
+        None => bail!(
+            "the cache has no driver compatible with Microsoft Edge {}, which needs a {}.x.x.x driver",
+            browser,
+            browser.major
+        ),
+    }
+}
+
+#[test]
+fn browser_versions_parse() {
+    assert_eq!(
+        parse_browser_version("Microsoft Edge 118.0.2088.76 unknown\n")
+            .unwrap()
+            .to_string(),
+        "118.0.2088.76"
+    );
+    assert_eq!(
+        parse_browser_version("118.0.2088.76").unwrap().to_string(),
+        "118.0.2088.76"
+    );
+    assert!(parse_browser_version("Microsoft Edge").is_err());
+}
+
+#[test]
+fn compatible_drivers_are_selected() {
+    let version = |s: &str| s.parse::<Version>().unwrap();
+    let available = || {
+        [
+            "117.0.2045.60",
+            "118.0.2088.46",
+            "118.0.2088.76",
+            "118.0.2088.88",
+            "119.0.2151.44",
+        ]
+        .map(version)
+    };
+    let select = |browser| select_compatible(version(browser), available()).unwrap();
+
+    assert_eq!(select("118.0.2088.76"), version("118.0.2088.76"));
+    assert_eq!(select("118.0.2088.80"), version("118.0.2088.76"));
+    assert_eq!(select("118.0.2000.0"), version("118.0.2088.46"));
+
+    let error = select_compatible(version("120.0.2210.61"), available()).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "the cache has no driver compatible with Microsoft Edge 120.0.2210.61, which needs a 120.x.x.x driver"
+    );
+}
@@ -0,0 +1,212 @@
+//! Output in the schemas of Chrome for Testing's JSON API endpoints, so tools written against it
+//! can consume msedgedriver unchanged. See <https://github.com/GoogleChromeLabs/chrome-for-testing>.
+
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::{
+    channels::{Channel, Channels},
+    timestamp::Timestamp,
+    version::Version,
+    Output,
+};
+
+/// `known-good-versions-with-downloads.json`
+#[derive(Debug, Serialize)]
+pub struct KnownGoodVersions<'a> {
+    /// When the data last changed, see [`timestamp`].
+    pub timestamp: Timestamp,
+    /// Oldest first.
+    pub versions: Vec<KnownGoodVersion<'a>>,
+}
+
+/// A version in `known-good-versions-with-downloads.json`.
+#[derive(Debug, Serialize)]
+pub struct KnownGoodVersion<'a> {
+    /// The version, without a revision.
+    pub version: Version,
+    /// What can be downloaded for this version.
+    pub downloads: Downloads<'a>,
+}
+
+/// The downloads of a version, by binary.
+#[derive(Debug, Serialize)]
+pub struct Downloads<'a> {
+    /// The driver archives, one per platform.
+    pub msedgedriver: Vec<Download<'a>>,
+}
+
+/// A single driver archive.
+#[derive(Debug, Serialize)]
+pub struct Download<'a> {
+    /// The platform, named the way Chrome for Testing names it.
+    pub platform: &'a str,
+    /// Where the archive is downloaded from.
+    pub url: &'a str,
+}
+
+/// `last-known-good-versions.json`
+#[derive(Debug, Serialize)]
+pub struct LastKnownGoodVersions {
+    /// When the data last changed, see [`timestamp`].
+    pub timestamp: Timestamp,
+    /// The version of each channel, by its Chrome for Testing name (`Stable`, `Beta`, ...).
+    pub channels: BTreeMap<&'static str, LastKnownGoodVersion>,
+}
+
+/// The version a channel points at, in `last-known-good-versions.json`.
+#[derive(Debug, Serialize)]
+pub struct LastKnownGoodVersion {
+    /// The name of the channel, the same as its key.
+    pub channel: &'static str,
+    /// The version the channel points at.
+    pub version: Version,
+}
+
+impl<'a> KnownGoodVersions<'a> {
+    /// Every version in `output` with the drivers that can still be downloaded.
+    pub fn new(output: &'a Output, timestamp: Timestamp) -> Self {
+        let versions = output
+            .0
+            .iter()
+            .filter_map(|(version, platforms)| {
+                let mut msedgedriver: Vec<_> = platforms
+                    .iter()
+                    .filter(|(_, properties)| properties.removed_upstream_at.is_none())
+                    .map(|(platform, properties)| Download {
+                        platform: platform.cft_name(),
+                        url: &properties.url,
+                    })
+                    .collect();
+                msedgedriver.sort_by_key(|download| download.platform);
+
+                (!msedgedriver.is_empty()).then_some(KnownGoodVersion {
+                    version: *version,
+                    downloads: Downloads { msedgedriver },
+                })
+            })
+            .collect();
+
+        Self {
+            timestamp,
+            versions,
+        }
+    }
+}
+
+impl LastKnownGoodVersions {
+    /// The version each channel currently points at, regardless of OS.
+    pub fn new(channels: &Channels, timestamp: Timestamp) -> Self {
+        let channels = channels
+            .channels
+            .iter()
+            .filter_map(|(channel, targets)| {
+                let name = channel_name(*channel);
+                let version = targets.any?;
+                Some((
+                    name,
+                    LastKnownGoodVersion {
+                        channel: name,
+                        version,
+                    },
+                ))
+            })
+            .collect();
+
+        Self {
+            timestamp,
+            channels,
+        }
+    }
+}
+
+/// The `Last-Modified` of the newest driver in `output`.
+///
+/// This is used instead of the current time so the same listing always builds the same output.
+pub fn timestamp(output: &Output) -> Timestamp {
+    output
+        .0
+        .values()
+        .flat_map(|platforms| platforms.values())
+        .filter_map(|properties| properties.last_modified)
+        .max()
+        .unwrap_or_default()
+}
+
+fn channel_name(channel: Channel) -> &'static str {
+    match channel {
+        Channel::Stable => "Stable",
+        Channel::Beta => "Beta",
+        Channel::Dev => "Dev",
+        Channel::Canary => "Canary",
+    }
+}
+
+#[test]
+fn known_good_versions_schema() {
+    use crate::{fixture::driver, platform::Platform};
+
+    let output = Output(BTreeMap::from([
+        (
+            "118.0.2088.76".parse().unwrap(),
+            BTreeMap::from([
+                (Platform::Win64, driver("https://x/win64.zip", false)),
+                (Platform::Mac64M1, driver("https://x/mac.zip", false)),
+            ]),
+        ),
+        (
+            "90.0.818.0".parse().unwrap(),
+            BTreeMap::from([(Platform::Win64, driver("https://x/old.zip", true))]),
+        ),
+    ]));
+
+    assert_eq!(
+        serde_json::to_value(KnownGoodVersions::new(
+            &output,
+            "2023-10-16T08:09:14Z".parse().unwrap()
+        ))
+        .unwrap(),
+        serde_json::json!({
+            "timestamp": "2023-10-16T08:09:14Z",
+            "versions": [{
+                "version": "118.0.2088.76",
+                "downloads": {
+                    "msedgedriver": [
+                        { "platform": "mac-arm64", "url": "https://x/mac.zip" },
+                        { "platform": "win64", "url": "https://x/win64.zip" },
+                    ]
+                }
+            }]
+        })
+    );
+}
+
+#[test]
+fn last_known_good_versions_schema() {
+    use crate::channels::Pointer;
+
+    let mut channels = Channels::default();
+    channels.insert(
+        Pointer::Channel(Channel::Stable, None),
+        "118.0.2088.76".parse().unwrap(),
+    );
+    channels.insert(
+        Pointer::Channel(Channel::Beta, Some(crate::platform::Os::Linux)),
+        "119.0.2151.32".parse().unwrap(),
+    );
+
+    assert_eq!(
+        serde_json::to_value(LastKnownGoodVersions::new(
+            &channels,
+            "2023-10-16T08:09:14Z".parse().unwrap()
+        ))
+        .unwrap(),
+        serde_json::json!({
+            "timestamp": "2023-10-16T08:09:14Z",
+            "channels": {
+                "Stable": { "channel": "Stable", "version": "118.0.2088.76" },
+            }
+        })
+    );
+}
@@ -0,0 +1,269 @@
+//! Resolving the `LATEST_*` pointer blobs into the release channels published in `channels.json`.
+
+use std::{collections::BTreeMap, io::Read, str::FromStr};
+
+use anyhow::{bail, Error, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    classify::{SkipReason, SkippedBlob},
+    platform::Os,
+    retry::RetryPolicy,
+    version::Version,
+    Blob, USER_AGENT,
+};
+
+/// A release channel of Microsoft Edge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Channel {
+    /// The stable release.
+    Stable,
+    /// The beta channel.
+    Beta,
+    /// The dev channel.
+    Dev,
+    /// The daily canary builds.
+    Canary,
+}
+
+impl FromStr for Channel {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "stable" => Ok(Self::Stable),
+            "beta" => Ok(Self::Beta),
+            "dev" => Ok(Self::Dev),
+            "canary" => Ok(Self::Canary),
+            _ => bail!(
+                "unknown channel {:?}, expected stable, beta, dev or canary",
+                s
+            ),
+        }
+    }
+}
+
+/// A `LATEST_*` blob, whose content is the version it currently points at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pointer {
+    /// `LATEST_<CHANNEL>`, optionally suffixed with `_<OS>`.
+    Channel(Channel, Option<Os>),
+    /// `LATEST_RELEASE_<major>`, optionally suffixed with `_<OS>`.
+    Release(u32, Option<Os>),
+}
+
+impl Pointer {
+    /// Parse a top-level blob name like `LATEST_STABLE` or `LATEST_RELEASE_118_LINUX`.
+    pub fn parse(name: &str) -> Option<Self> {
+        let rest = name.strip_prefix("LATEST_")?;
+        let (rest, os) = match rest.rsplit_once('_') {
+            Some((head, "WINDOWS")) => (head, Some(Os::Windows)),
+            Some((head, "MACOS")) => (head, Some(Os::Mac)),
+            Some((head, "LINUX")) => (head, Some(Os::Linux)),
+            _ => (rest, None),
+        };
+
+        let pointer = match rest {
+            "STABLE" => Self::Channel(Channel::Stable, os),
+            "BETA" => Self::Channel(Channel::Beta, os),
+            "DEV" => Self::Channel(Channel::Dev, os),
+            "CANARY" => Self::Channel(Channel::Canary, os),
+            _ => Self::Release(rest.strip_prefix("RELEASE_")?.parse().ok()?, os),
+        };
+
+        Some(pointer)
+    }
+}
+
+/// Every resolved pointer, as published in `channels.json`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Channels {
+    /// The versions of each channel, from `LATEST_<CHANNEL>`.
+    pub channels: BTreeMap<Channel, Targets>,
+    /// The newest version of each major release, from `LATEST_RELEASE_<major>`.
+    pub releases: BTreeMap<u32, Targets>,
+}
+
+/// The versions a channel or major release points at, regardless of OS and per OS.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Targets {
+    /// The version of the pointer without an OS suffix.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub any: Option<Version>,
+    /// The versions of the pointers with an OS suffix, like `LATEST_STABLE_LINUX`.
+    #[serde(flatten)]
+    pub os: BTreeMap<Os, Version>,
+}
+
+impl Channels {
+    /// Record the version `pointer` points at.
+    pub fn insert(&mut self, pointer: Pointer, version: Version) {
+        let (targets, os) = match pointer {
+            Pointer::Channel(channel, os) => (self.channels.entry(channel).or_default(), os),
+            Pointer::Release(major, os) => (self.releases.entry(major).or_default(), os),
+        };
+
+        match os {
+            Some(os) => {
+                targets.os.insert(os, version);
+            }
+            None => targets.any = Some(version),
+        }
+    }
+}
+
+/// Download every pointer blob and collect the versions they point at.
+///
+/// Pointers that can't be fetched or don't contain a version are returned as skipped blobs, with
+/// the reason they couldn't be resolved described in `warnings`.
+pub fn resolve(
+    pointers: Vec<(Pointer, Blob)>,
+    policy: &RetryPolicy,
+    warnings: &mut Vec<String>,
+) -> (Channels, Vec<SkippedBlob>) {
+    let mut channels = Channels::default();
+    let mut skipped = Vec::new();
+
+    for (pointer, blob) in pointers {
+        let version = fetch_pointer(&blob.url, policy)
+            .and_then(|bytes| decode_pointer(&bytes))
+            .and_then(|content| content.trim().parse::<Version>());
+
+        match version {
+            Ok(version) => channels.insert(pointer, version),
+            Err(e) => {
+                warnings.push(format!("unable to resolve {}: {}", blob.name, e));
+                skipped.push(SkippedBlob {
+                    name: blob.name,
+                    url: blob.url,
+                    reason: SkipReason::UnresolvedPointer,
+                });
+            }
+        }
+    }
+
+    (channels, skipped)
+}
+
+fn fetch_pointer(url: &str, policy: &RetryPolicy) -> Result<Vec<u8>> {
+    policy.run(url, || {
+        let mut bytes = Vec::new();
+        ureq::get(url)
+            .set("User-Agent", USER_AGENT)
+            .call()?
+            .into_reader()
+            .read_to_end(&mut bytes)?;
+
+        Ok(bytes)
+    })
+}
+
+/// Decode the content of a pointer blob, which upstream stores as UTF-16 with a byte order mark.
+fn decode_pointer(bytes: &[u8]) -> Result<String> {
+    let utf16 = |bytes: &[u8], from_bytes: fn([u8; 2]) -> u16| -> Result<String> {
+        if !bytes.len().is_multiple_of(2) {
+            bail!("odd number of bytes in UTF-16 content");
+        }
+
+        let units: Vec<u16> = bytes
+            .chunks_exact(2)
+            .map(|pair| from_bytes([pair[0], pair[1]]))
+            .collect();
+        Ok(String::from_utf16(&units)?)
+    };
+
+    match bytes {
+        [0xFF, 0xFE, rest @ ..] => utf16(rest, u16::from_le_bytes),
+        [0xFE, 0xFF, rest @ ..] => utf16(rest, u16::from_be_bytes),
+        [0xEF, 0xBB, 0xBF, rest @ ..] => Ok(std::str::from_utf8(rest)?.into()),
+        // UTF-16 without a byte order mark, which is little endian on the machines producing these
+        _ if bytes.contains(&0) => utf16(bytes, u16::from_le_bytes),
+        _ => Ok(std::str::from_utf8(bytes)?.into()),
+    }
+}
+
+#[test]
+fn pointer_names() {
+    assert_eq!(
+        Pointer::parse("LATEST_STABLE"),
+        Some(Pointer::Channel(Channel::Stable, None))
+    );
+    assert_eq!(
+        Pointer::parse("LATEST_CANARY_MACOS"),
+        Some(Pointer::Channel(Channel::Canary, Some(Os::Mac)))
+    );
+    assert_eq!(
+        Pointer::parse("LATEST_RELEASE_118"),
+        Some(Pointer::Release(118, None))
+    );
+    assert_eq!(
+        Pointer::parse("LATEST_RELEASE_118_LINUX"),
+        Some(Pointer::Release(118, Some(Os::Linux)))
+    );
+    assert_eq!(Pointer::parse("LATEST_NIGHTLY"), None);
+    assert_eq!(Pointer::parse("LATEST_RELEASE_x_LINUX"), None);
+    assert_eq!(Pointer::parse("STABLE"), None);
+}
+
+#[test]
+fn pointer_encodings() {
+    let le: Vec<u8> = [0xFF, 0xFE]
+        .into_iter()
+        .chain(
+            "118.0.2088.76\r\n"
+                .encode_utf16()
+                .flat_map(u16::to_le_bytes),
+        )
+        .collect();
+    let be: Vec<u8> = [0xFE, 0xFF]
+        .into_iter()
+        .chain("118.0.2088.76".encode_utf16().flat_map(u16::to_be_bytes))
+        .collect();
+
+    assert_eq!(decode_pointer(&le).unwrap().trim(), "118.0.2088.76");
+    assert_eq!(decode_pointer(&be).unwrap(), "118.0.2088.76");
+    assert_eq!(decode_pointer(&le[2..]).unwrap().trim(), "118.0.2088.76");
+    assert_eq!(decode_pointer(b"118.0.2088.76").unwrap(), "118.0.2088.76");
+}
+
+#[test]
+fn pointers_resolve_into_channels() {
+    use crate::stub::{Reply, StubServer};
+
+    let utf16 = |s: &str| -> Vec<u8> {
+        [0xFF, 0xFE]
+            .into_iter()
+            .chain(s.encode_utf16().flat_map(u16::to_le_bytes))
+            .collect()
+    };
+    let server = StubServer::new(vec![
+        Reply::new(200, utf16("118.0.2088.76")),
+        Reply::new(200, utf16("117.0.2045.60")),
+        Reply::new(200, utf16("not a version")),
+    ]);
+    let blob = |name: &str| Blob {
+        name: name.into(),
+        url: server.url(&format!("/{}", name)),
+        ..Default::default()
+    };
+
+    let pointers = ["LATEST_STABLE", "LATEST_RELEASE_117_LINUX", "LATEST_BETA"]
+        .into_iter()
+        .map(|name| (Pointer::parse(name).unwrap(), blob(name)))
+        .collect();
+    let mut warnings = Vec::new();
+    let (channels, skipped) = resolve(pointers, &RetryPolicy::default(), &mut warnings);
+
+    assert_eq!(
+        serde_json::to_value(&channels).unwrap(),
+        serde_json::json!({
+            "channels": { "stable": { "any": "118.0.2088.76" } },
+            "releases": { "117": { "linux": "117.0.2045.60" } },
+        })
+    );
+    assert_eq!(skipped.len(), 1);
+    assert_eq!(skipped[0].reason, SkipReason::UnresolvedPointer);
+    assert_eq!(warnings.len(), 1);
+    assert!(warnings[0].starts_with("unable to resolve LATEST_BETA: "));
+}
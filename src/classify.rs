@@ -0,0 +1,214 @@
+//! Sorting the blobs of the listing into drivers, pointers and the ones that are skipped.
+
+use std::str::FromStr;
+
+use anyhow::{bail, Result};
+use serde::Serialize;
+
+use crate::{channels::Pointer, platform::Platform, version::Version};
+
+/// Why a blob in the container was not treated as a driver archive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SkipReason {
+    /// Not inside a version directory, and not a known `LATEST_*` pointer either.
+    TopLevel,
+    /// Nested deeper than `<version>/<file>`.
+    NestedPath,
+    /// The directory name is not a four-part version.
+    InvalidVersion,
+    /// Not an `edgedriver_*.zip` archive, e.g. a `.txt` file next to the drivers.
+    NotADriverArchive,
+    /// A `LATEST_*` pointer whose content couldn't be fetched or isn't a version.
+    UnresolvedPointer,
+}
+
+/// What a blob in the container turned out to be.
+#[derive(Debug, PartialEq, Eq)]
+pub enum BlobKind {
+    /// An `edgedriver_<platform>.zip` archive.
+    Driver(Version, Platform),
+    /// A `LATEST_*` pointer to a version.
+    Pointer(Pointer),
+}
+
+/// A blob that was left out of the output, as published in `skipped.json`.
+#[derive(Debug, Serialize)]
+pub struct SkippedBlob {
+    /// The path of the blob in the container.
+    pub name: String,
+    /// Where the blob can be downloaded from.
+    pub url: String,
+    /// Why the blob was skipped.
+    pub reason: SkipReason,
+}
+
+/// How the build reacts to blobs it had to skip, and to other problems with the listed blobs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Strictness {
+    /// Only record them in the output, e.g. in `skipped.json`.
+    Allow,
+    /// Also print a warning for each of them.
+    #[default]
+    Warn,
+    /// Fail the build.
+    Deny,
+}
+
+impl FromStr for Strictness {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "allow" => Ok(Self::Allow),
+            "warn" => Ok(Self::Warn),
+            "deny" => Ok(Self::Deny),
+            _ => bail!("unknown strictness {:?}, expected allow, warn or deny", s),
+        }
+    }
+}
+
+impl Strictness {
+    /// Report the skipped blobs according to this strictness level, as `warnings` or as an error.
+    pub fn check(self, skipped: &[SkippedBlob], warnings: &mut Vec<String>) -> Result<()> {
+        match self {
+            Self::Allow => {}
+            Self::Warn => {
+                for blob in skipped {
+                    warnings.push(format!("skipped blob {} ({:?})", blob.name, blob.reason));
+                }
+            }
+            Self::Deny => {
+                if let Some(blob) = skipped.first() {
+                    bail!(
+                        "skipped {} blob(s), first: {} ({:?})",
+                        skipped.len(),
+                        blob.name,
+                        blob.reason
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Report other problems with the listed blobs, e.g. malformed properties, according to this
+    /// strictness level. `what` describes them all in the error, like `"malformed properties"`.
+    pub fn check_problems(
+        self,
+        what: &str,
+        problems: &[String],
+        warnings: &mut Vec<String>,
+    ) -> Result<()> {
+        match self {
+            Self::Allow => {}
+            Self::Warn => warnings.extend_from_slice(problems),
+            Self::Deny => {
+                if let Some(problem) = problems.first() {
+                    bail!("found {} {}, first: {}", problems.len(), what, problem);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Classify a blob by its name, or explain why it doesn't belong in the output.
+pub fn classify(name: &str) -> Result<BlobKind, SkipReason> {
+    if let Some(pointer) = Pointer::parse(name) {
+        return Ok(BlobKind::Pointer(pointer));
+    }
+
+    parse_version_and_platform(name).map(|(version, platform)| BlobKind::Driver(version, platform))
+}
+
+/// Parse a blob name of the form `<version>/edgedriver_<platform>.zip`.
+///
+/// Platforms this crate doesn't know yet are parsed too, see [`Platform::is_known`].
+pub fn parse_version_and_platform(s: &str) -> Result<(Version, Platform), SkipReason> {
+    let (version_raw, platform_raw) = s.split_once('/').ok_or(SkipReason::TopLevel)?;
+
+    if platform_raw.contains('/') {
+        return Err(SkipReason::NestedPath);
+    }
+
+    let version = version_raw
+        .parse()
+        .map_err(|_| SkipReason::InvalidVersion)?;
+
+    let platform = Platform::from_name(
+        platform_raw
+            .strip_prefix("edgedriver_")
+            .and_then(|platform| platform.strip_suffix(".zip"))
+            .ok_or(SkipReason::NotADriverArchive)?,
+    );
+
+    Ok((version, platform))
+}
+
+#[test]
+fn version_and_platform() {
+    let (version, platform) =
+        parse_version_and_platform("100.0.1154.0/edgedriver_arm64.zip").unwrap();
+
+    assert_eq!(version.to_string(), "100.0.1154.0");
+    assert_eq!(platform, Platform::Arm64);
+
+    assert_eq!(
+        parse_version_and_platform("stable/edgedriver_arm64.zip"),
+        Err(SkipReason::InvalidVersion)
+    );
+}
+
+#[test]
+fn unexpected_names_are_classified() {
+    let reason = |name| parse_version_and_platform(name).unwrap_err();
+
+    assert_eq!(reason("LATEST_STABLE"), SkipReason::TopLevel);
+    assert_eq!(
+        reason("100.0.1154.0/nested/edgedriver_arm64.zip"),
+        SkipReason::NestedPath
+    );
+    assert_eq!(
+        reason("100.0.1154.0/credits.html"),
+        SkipReason::NotADriverArchive
+    );
+    assert_eq!(
+        reason("100.0.1154.0/edgedriver_arm64.txt"),
+        SkipReason::NotADriverArchive
+    );
+}
+
+#[test]
+fn pointers_are_not_skipped() {
+    use crate::channels::Channel;
+
+    assert_eq!(
+        classify("LATEST_STABLE"),
+        Ok(BlobKind::Pointer(Pointer::Channel(Channel::Stable, None)))
+    );
+    assert_eq!(classify("LATEST_NIGHTLY"), Err(SkipReason::TopLevel));
+    assert!(matches!(
+        classify("100.0.1154.0/edgedriver_arm64.zip"),
+        Ok(BlobKind::Driver(..))
+    ));
+}
+
+#[test]
+fn deny_fails_on_skipped_blobs() {
+    let skipped = [SkippedBlob {
+        name: "LATEST_NIGHTLY".into(),
+        url: String::new(),
+        reason: SkipReason::TopLevel,
+    }];
+
+    let mut warnings = Vec::new();
+    assert!(Strictness::Allow.check(&skipped, &mut warnings).is_ok());
+    assert!(warnings.is_empty());
+    assert!(Strictness::Warn.check(&skipped, &mut warnings).is_ok());
+    assert_eq!(warnings, ["skipped blob LATEST_NIGHTLY (TopLevel)"]);
+    assert!(Strictness::Deny.check(&skipped, &mut warnings).is_err());
+    assert!(Strictness::Deny.check(&[], &mut warnings).is_ok());
+}
@@ -0,0 +1,462 @@
+//! Resolving and installing a driver from a published cache, for consumers of it.
+
+use std::{
+    collections::BTreeMap,
+    fs::{create_dir_all, read_to_string, remove_file, File},
+    io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::{anyhow, ensure, Context, Error, Result};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use zip::ZipArchive;
+
+use crate::{
+    browser::select_compatible,
+    channels::{Channel, Channels},
+    drivers::DriverCache,
+    index::version_file,
+    platform::Platform,
+    retry::RetryPolicy,
+    verify::download,
+    version::Version,
+    Properties, USER_AGENT,
+};
+
+/// Where a published cache is read from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Location {
+    /// The base URL of a deployed cache, e.g. `https://example.com/msedgedriver`.
+    Url(String),
+    /// A local `dist` directory.
+    Dir(PathBuf),
+}
+
+impl Location {
+    /// A URL if `s` starts with `http://` or `https://`, a directory otherwise.
+    pub fn new(s: &str) -> Self {
+        if s.starts_with("http://") || s.starts_with("https://") {
+            Self::Url(s.trim_end_matches('/').into())
+        } else {
+            Self::Dir(s.into())
+        }
+    }
+}
+
+/// Which driver to resolve.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Request {
+    /// Exactly this version.
+    Version(Version),
+    /// The newest version of a major release.
+    Major(u32),
+    /// The version a release channel currently points at.
+    Channel(Channel),
+    /// The driver compatible with this browser version, see [`select_compatible`].
+    Browser(Version),
+}
+
+impl FromStr for Request {
+    type Err = Error;
+
+    /// Parse a full version (`118.0.2088.76`), a major release (`118`) or a channel (`stable`).
+    fn from_str(s: &str) -> Result<Self> {
+        if let Ok(channel) = s.parse() {
+            return Ok(Self::Channel(channel));
+        }
+        if !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit()) {
+            return Ok(Self::Major(s.parse()?));
+        }
+
+        s.parse().map(Self::Version).map_err(|_| {
+            anyhow!(
+                "invalid request {:?}, expected a version, a major release or a channel",
+                s
+            )
+        })
+    }
+}
+
+/// A driver a [`Request`] resolved to.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Resolved {
+    /// The version of the driver.
+    pub version: Version,
+    /// The properties of the driver, as published in its version file.
+    #[serde(flatten)]
+    pub properties: Properties,
+}
+
+/// The parts of `majors/<major>.json` the client needs.
+#[derive(Deserialize)]
+struct MajorFile {
+    platforms: BTreeMap<Platform, Resolved>,
+}
+
+/// The parts of `platforms/<platform>.json` the client needs.
+#[derive(Deserialize)]
+struct PlatformFile {
+    versions: Vec<Resolved>,
+}
+
+/// Reads a published cache and installs drivers from it.
+pub struct Client {
+    location: Location,
+    policy: RetryPolicy,
+}
+
+impl Client {
+    /// A client of the published cache at `location`, retrying failed requests with `policy`.
+    pub fn new(location: Location, policy: RetryPolicy) -> Self {
+        Self { location, policy }
+    }
+
+    /// Resolve `request` and install its driver for `platform` into `cache`, returning the path of
+    /// the extracted `msedgedriver` executable. A driver that is already cached isn't downloaded
+    /// again.
+    pub fn install(
+        &self,
+        request: &Request,
+        platform: &Platform,
+        cache: &DriverCache,
+    ) -> Result<PathBuf> {
+        let driver = self.resolve(request, platform)?;
+        cache.install(self, &driver, platform)
+    }
+
+    /// Look up the properties of the driver for `platform` that `request` points at.
+    pub fn resolve(&self, request: &Request, platform: &Platform) -> Result<Resolved> {
+        let resolved = match request {
+            Request::Version(version) => self.resolve_version(*version, platform)?,
+            Request::Major(major) => {
+                let mut file: MajorFile = self.read(&format!("majors/{}.json", major))?;
+                file.platforms.remove(platform).ok_or_else(|| {
+                    anyhow!("major release {} has no driver for {}", major, platform)
+                })?
+            }
+            Request::Channel(channel) => {
+                let channels: Channels = self.read("channels.json")?;
+                let targets = channels
+                    .channels
+                    .get(channel)
+                    .ok_or_else(|| anyhow!("the cache doesn't know the {:?} channel", channel))?;
+                let version = platform
+                    .os()
+                    .and_then(|os| targets.os.get(&os))
+                    .or(targets.any.as_ref())
+                    .ok_or_else(|| {
+                        anyhow!("the {:?} channel has no version for {}", channel, platform)
+                    })?;
+                self.resolve_version(*version, platform)?
+            }
+            Request::Browser(browser) => {
+                let file: PlatformFile = self.read(&format!("platforms/{}.json", platform))?;
+                let mut available: Vec<_> = file
+                    .versions
+                    .into_iter()
+                    .filter(|driver| driver.properties.removed_upstream_at.is_none())
+                    .collect();
+                let version =
+                    select_compatible(*browser, available.iter().map(|driver| driver.version))
+                        .with_context(|| format!("no compatible driver for {}", platform))?;
+
+                let i = available
+                    .iter()
+                    .position(|driver| driver.version == version)
+                    .expect("the selected version is one of the available ones");
+                available.swap_remove(i)
+            }
+        };
+
+        ensure!(
+            resolved.properties.removed_upstream_at.is_none(),
+            "the {} driver for {} was removed upstream",
+            resolved.version,
+            platform
+        );
+        Ok(resolved)
+    }
+
+    fn resolve_version(&self, version: Version, platform: &Platform) -> Result<Resolved> {
+        let mut platforms: BTreeMap<Platform, Properties> = self.read(&version_file(&version))?;
+        let properties = platforms
+            .remove(platform)
+            .ok_or_else(|| anyhow!("version {} has no driver for {}", version, platform))?;
+
+        Ok(Resolved {
+            version,
+            properties,
+        })
+    }
+
+    /// Download the archive of `driver`, check it against its listed size and MD5, and extract
+    /// the `msedgedriver` executable into `target`.
+    pub fn download(
+        &self,
+        driver: &Resolved,
+        platform: &Platform,
+        target: &Path,
+    ) -> Result<PathBuf> {
+        create_dir_all(target)?;
+        let archive = target.join(format!(".edgedriver_{}_{}.zip", driver.version, platform));
+
+        let result = download(&driver.properties.url, &archive, &self.policy).and_then(|digests| {
+            if let Some(length) = driver.properties.content_length {
+                ensure!(
+                    digests.size == length,
+                    "downloaded {} bytes, but the cache lists {}",
+                    digests.size,
+                    length
+                );
+            }
+            if let Some(md5) = driver.properties.md5_digest() {
+                ensure!(
+                    digests.md5.0 == md5,
+                    "downloaded archive has MD5 {}, but the cache lists {}",
+                    digests.md5,
+                    hex::encode(md5)
+                );
+            }
+
+            extract_driver(&archive, target)
+        });
+
+        let _ = remove_file(&archive);
+        result.with_context(|| format!("failed to install {}", driver.properties.url))
+    }
+
+    fn read<T: DeserializeOwned>(&self, path: &str) -> Result<T> {
+        let content = match &self.location {
+            Location::Url(base) => {
+                let url = format!("{}/{}", base, path);
+                self.policy.run(&url, || {
+                    Ok(ureq::get(&url)
+                        .set("User-Agent", USER_AGENT)
+                        .call()?
+                        .into_string()?)
+                })?
+            }
+            Location::Dir(dir) => read_to_string(dir.join(path))
+                .with_context(|| format!("failed to read {}", dir.join(path).display()))?,
+        };
+
+        serde_json::from_str(&content).with_context(|| format!("failed to parse {}", path))
+    }
+}
+
+/// Extract the `msedgedriver` (or `msedgedriver.exe`) executable of an archive into `target`.
+fn extract_driver(archive: &Path, target: &Path) -> Result<PathBuf> {
+    let mut zip = ZipArchive::new(File::open(archive)?)?;
+
+    // the executable is at the root of the archive, but don't count on it
+    let (entry, file_name) = zip
+        .file_names()
+        .find_map(|name| {
+            let file_name = Path::new(name).file_name()?.to_str()?;
+            matches!(file_name, "msedgedriver" | "msedgedriver.exe")
+                .then(|| (name.to_owned(), file_name.to_owned()))
+        })
+        .ok_or_else(|| anyhow!("the archive doesn't contain msedgedriver"))?;
+
+    let mut file = zip.by_name(&entry)?;
+    let path = target.join(file_name);
+    io::copy(&mut file, &mut File::create(&path)?)?;
+
+    #[cfg(unix)]
+    {
+        use std::{fs::set_permissions, os::unix::fs::PermissionsExt};
+        set_permissions(&path, PermissionsExt::from_mode(0o755))?;
+    }
+
+    Ok(path)
+}
+
+#[cfg(test)]
+pub(crate) fn driver_archive() -> Vec<u8> {
+    use std::io::Write;
+    use zip::{write::FileOptions, ZipWriter};
+
+    let mut zip = ZipWriter::new(io::Cursor::new(Vec::new()));
+    zip.start_file("Driver_Notes/credits.html", FileOptions::default())
+        .unwrap();
+    zip.write_all(b"credits").unwrap();
+    zip.start_file("msedgedriver", FileOptions::default())
+        .unwrap();
+    zip.write_all(b"#!/bin/sh\n").unwrap();
+    zip.finish().unwrap().into_inner()
+}
+
+#[test]
+fn requests_parse() {
+    assert_eq!(
+        "stable".parse::<Request>().unwrap(),
+        Request::Channel(Channel::Stable)
+    );
+    assert_eq!("118".parse::<Request>().unwrap(), Request::Major(118));
+    assert_eq!(
+        "118.0.2088.76".parse::<Request>().unwrap(),
+        Request::Version("118.0.2088.76".parse().unwrap())
+    );
+    assert!("118.0".parse::<Request>().is_err());
+}
+
+#[test]
+fn installs_a_driver_from_a_local_dist() {
+    use crate::stub::{Reply, StubServer};
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use md5::{Digest, Md5};
+    use std::fs::{create_dir, write};
+
+    let archive = driver_archive();
+    let server = StubServer::new(vec![
+        Reply::new(200, archive.clone()),
+        Reply::new(200, b"truncated".to_vec()),
+    ]);
+
+    let tmp = tempfile::tempdir().unwrap();
+    let dist = tmp.path().join("dist");
+    create_dir(&dist).unwrap();
+    create_dir(dist.join("majors")).unwrap();
+    create_dir(dist.join("versions")).unwrap();
+
+    let properties = serde_json::json!({
+        "url": server.url("/100.0.1154.0/edgedriver_linux64.zip"),
+        "etag": "0x1",
+        "md5": STANDARD.encode(Md5::digest(&archive)),
+        "md5Hex": hex::encode(Md5::digest(&archive)),
+        "contentLength": archive.len(),
+    });
+    let mut driver = properties.clone();
+    driver["version"] = "100.0.1154.0".into();
+    driver["platform"] = "linux64".into();
+    write(
+        dist.join("versions").join("100.0.1154.0.json"),
+        serde_json::json!({ "linux64": properties }).to_string(),
+    )
+    .unwrap();
+    write(
+        dist.join("majors").join("100.json"),
+        serde_json::json!({ "major": 100, "platforms": { "linux64": driver } }).to_string(),
+    )
+    .unwrap();
+
+    let client = Client::new(
+        Location::new(dist.to_str().unwrap()),
+        RetryPolicy::default(),
+    );
+    let cache = DriverCache::new(tmp.path().join("drivers"));
+
+    let resolved = client
+        .resolve(&Request::Major(100), &Platform::Linux64)
+        .unwrap();
+    assert_eq!(resolved.version.to_string(), "100.0.1154.0");
+    assert!(client
+        .resolve(&Request::Major(100), &Platform::Win64)
+        .is_err());
+
+    let path = client
+        .install(&"100.0.1154.0".parse().unwrap(), &Platform::Linux64, &cache)
+        .unwrap();
+    assert_eq!(
+        path,
+        cache.root().join("100.0.1154.0/linux64/0x1/msedgedriver")
+    );
+    assert_eq!(read_to_string(&path).unwrap(), "#!/bin/sh\n");
+
+    // the cached driver is reused
+    let again = client
+        .install(&Request::Major(100), &Platform::Linux64, &cache)
+        .unwrap();
+    assert_eq!(again, path);
+    assert_eq!(server.requests().len(), 1);
+
+    let other = DriverCache::new(tmp.path().join("other"));
+    let error = client
+        .install(&Request::Major(100), &Platform::Linux64, &other)
+        .unwrap_err();
+    assert!(format!("{:#}", error).contains("but the cache lists"));
+    assert!(other.list(&mut Vec::new()).unwrap().is_empty());
+}
+
+#[test]
+fn md5_is_checked_without_md5_hex() {
+    use crate::{
+        properties::Base64,
+        stub::{Reply, StubServer},
+    };
+    use md5::{Digest, Md5};
+
+    let server = StubServer::new(vec![Reply::new(200, driver_archive())]);
+    let tmp = tempfile::tempdir().unwrap();
+    let client = Client::new(Location::Dir(tmp.path().into()), RetryPolicy::default());
+
+    // a cache published before `md5Hex` was added
+    let driver = Resolved {
+        version: "100.0.1154.0".parse().unwrap(),
+        properties: Properties {
+            url: server.url("/edgedriver_linux64.zip"),
+            md5: Some(Base64(Md5::digest(b"another archive").to_vec())),
+            ..Default::default()
+        },
+    };
+
+    let error = client
+        .download(&driver, &Platform::Linux64, tmp.path())
+        .unwrap_err();
+    assert!(format!("{:#}", error).contains("downloaded archive has MD5"));
+}
+
+#[test]
+fn browser_requests_skip_removed_drivers() {
+    use crate::{index::platform_indexes, write_json, Output};
+    use std::fs::create_dir;
+
+    let driver = |version: &str, platform: &str, removed: bool| {
+        let url = format!("https://example.com/{}/{}.zip", version, platform);
+        crate::fixture::driver(&url, removed)
+    };
+    let output = Output(BTreeMap::from([
+        (
+            "118.0.2088.46".parse().unwrap(),
+            BTreeMap::from([
+                (Platform::Linux64, driver("118.0.2088.46", "linux64", false)),
+                (Platform::Win64, driver("118.0.2088.46", "win64", false)),
+            ]),
+        ),
+        (
+            "118.0.2088.76".parse().unwrap(),
+            BTreeMap::from([(Platform::Win64, driver("118.0.2088.76", "win64", true))]),
+        ),
+        (
+            "119.0.2151.44".parse().unwrap(),
+            BTreeMap::from([(Platform::Linux64, driver("119.0.2151.44", "linux64", false))]),
+        ),
+    ]));
+
+    let tmp = tempfile::tempdir().unwrap();
+    create_dir(tmp.path().join("platforms")).unwrap();
+    for (platform, index) in platform_indexes(&output) {
+        let path = tmp.path().join(format!("platforms/{}.json", platform));
+        write_json(&path, &index).unwrap();
+    }
+
+    let client = Client::new(Location::Dir(tmp.path().into()), RetryPolicy::default());
+    let resolve = |browser: &str, platform| {
+        client.resolve(&Request::Browser(browser.parse().unwrap()), platform)
+    };
+
+    let resolved = resolve("118.0.2088.80", &Platform::Win64).unwrap();
+    assert_eq!(resolved.version.to_string(), "118.0.2088.46");
+    assert_eq!(
+        resolved.properties.url,
+        "https://example.com/118.0.2088.46/win64.zip"
+    );
+    let resolved = resolve("118.0.2088.76", &Platform::Linux64).unwrap();
+    assert_eq!(resolved.version.to_string(), "118.0.2088.46");
+    let resolved = resolve("119.0.2151.44", &Platform::Linux64).unwrap();
+    assert_eq!(resolved.version.to_string(), "119.0.2151.44");
+
+    assert!(resolve("119.0.2151.44", &Platform::Win64).is_err());
+    assert!(resolve("118.0.2088.76", &Platform::Mac64).is_err());
+}
@@ -0,0 +1,98 @@
+use std::{fs::read_to_string, path::Path};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+/// Environment variable overriding the primary listing URL.
+pub const URL_ENV: &str = "MSEDGEDRIVER_MANIFEST_URL";
+/// Environment variable with a comma separated list of fallback mirrors.
+pub const MIRRORS_ENV: &str = "MSEDGEDRIVER_MANIFEST_MIRRORS";
+/// Environment variable pointing at a config file, used when `--config` isn't given.
+pub const CONFIG_ENV: &str = "MSEDGEDRIVER_MANIFEST_CONFIG";
+
+/// Where to fetch the listing from, as set by a config file, the environment or the command line.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// The primary listing URL.
+    pub url: Option<String>,
+    /// Fallback listing URLs, tried in order when the primary one fails. These may also point at
+    /// the `manifest.xml` of a previously published cache.
+    #[serde(default)]
+    pub mirrors: Vec<String>,
+}
+
+impl Config {
+    /// Read a JSON config file, e.g. `{ "url": "...", "mirrors": ["..."] }`.
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = read_to_string(path)
+            .with_context(|| format!("failed to read config file {}", path.display()))?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("failed to parse config file {}", path.display()))
+    }
+
+    /// Read the settings from the environment, using `var` to look up a variable.
+    pub fn from_env(var: impl Fn(&str) -> Option<String>) -> Self {
+        Self {
+            url: var(URL_ENV).filter(|url| !url.is_empty()),
+            mirrors: var(MIRRORS_ENV)
+                .map(|mirrors| {
+                    mirrors
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|mirror| !mirror.is_empty())
+                        .map(Into::into)
+                        .collect()
+                })
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Use the settings of `self`, falling back to `other` for the ones that are not set.
+    pub fn or(self, other: Self) -> Self {
+        Self {
+            url: self.url.or(other.url),
+            mirrors: if self.mirrors.is_empty() {
+                other.mirrors
+            } else {
+                self.mirrors
+            },
+        }
+    }
+
+    /// The primary URL (or `default` if none is set) followed by the mirrors.
+    pub fn urls(self, default: &str) -> Vec<String> {
+        let primary = self.url.unwrap_or_else(|| default.into());
+        std::iter::once(primary).chain(self.mirrors).collect()
+    }
+}
+
+#[test]
+fn command_line_beats_environment_beats_file() {
+    let cli = Config {
+        url: Some("https://cli.example".into()),
+        mirrors: Vec::new(),
+    };
+    let env = Config::from_env(|name| match name {
+        URL_ENV => Some("https://env.example".into()),
+        MIRRORS_ENV => Some("https://a.example, https://b.example,".into()),
+        _ => None,
+    });
+    let file: Config = serde_json::from_str(
+        r#"{ "url": "https://file.example", "mirrors": ["https://c.example"] }"#,
+    )
+    .unwrap();
+
+    assert_eq!(
+        cli.or(env).or(file).urls("https://default.example"),
+        [
+            "https://cli.example",
+            "https://a.example",
+            "https://b.example"
+        ]
+    );
+    assert_eq!(
+        Config::default().urls("https://default.example"),
+        ["https://default.example"]
+    );
+}
@@ -0,0 +1,384 @@
+//! Comparing two snapshots of the cache, e.g. before deploying a new build.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    path::Path,
+};
+
+use anyhow::{anyhow, ensure, Result};
+use serde::Serialize;
+
+use crate::{
+    classify_blobs, merge, platform::Platform, retry::RetryPolicy, source::Source,
+    version::Version, Output, Properties,
+};
+
+/// What changed between two snapshots, as published in `changes.json`.
+#[derive(Debug, Default, Serialize)]
+pub struct Changes<'a> {
+    /// Versions that only the new snapshot has.
+    pub added: Vec<VersionChange<'a>>,
+    /// Versions that only the old snapshot has.
+    pub removed: Vec<VersionChange<'a>>,
+    /// Drivers that only the new snapshot has, of versions that both have.
+    #[serde(rename = "addedDrivers")]
+    pub added_drivers: Vec<VersionChange<'a>>,
+    /// Drivers that only the old snapshot has, of versions that both have.
+    #[serde(rename = "removedDrivers")]
+    pub removed_drivers: Vec<VersionChange<'a>>,
+    /// Platforms that no version of the old snapshot has a driver for.
+    #[serde(rename = "newPlatforms")]
+    pub new_platforms: Vec<&'a Platform>,
+    /// Drivers whose content changed without a new version, which should never happen upstream.
+    #[serde(rename = "changedInPlace")]
+    pub changed_in_place: Vec<ChangedDriver<'a>>,
+    /// Drivers with properties that only the new snapshot has a value for, e.g. an MD5 that
+    /// upstream computed later. Unlike changes in place, these are harmless.
+    #[serde(rename = "filledIn")]
+    pub filled_in: Vec<ChangedDriver<'a>>,
+}
+
+/// The drivers of a version that were added or removed.
+#[derive(Debug, Serialize)]
+pub struct VersionChange<'a> {
+    /// The version the drivers belong to.
+    pub version: Version,
+    /// The platforms of the drivers, sorted by name.
+    pub platforms: Vec<&'a Platform>,
+}
+
+/// A driver that changed in place, or had properties filled in.
+#[derive(Debug, Serialize)]
+pub struct ChangedDriver<'a> {
+    /// The version of the driver.
+    pub version: Version,
+    /// The platform of the driver.
+    pub platform: &'a Platform,
+    /// Every property that changed, or was filled in.
+    pub changes: Vec<FieldChange>,
+}
+
+/// A property of a driver that changed between two snapshots.
+#[derive(Debug, Serialize)]
+pub struct FieldChange {
+    /// The name of the property, as published.
+    pub field: &'static str,
+    /// The value in the old snapshot, unless the property was filled in.
+    pub old: Option<String>,
+    /// The value in the new snapshot, if it has one.
+    pub new: Option<String>,
+}
+
+impl<'a> Changes<'a> {
+    /// Compare the `old` snapshot with the `new` one.
+    pub fn new(old: &'a Output, new: &'a Output) -> Self {
+        let mut changes = Self::default();
+
+        for (version, platforms) in &new.0 {
+            match old.0.get(version) {
+                None => changes.added.push(VersionChange {
+                    version: *version,
+                    platforms: platforms.keys().collect(),
+                }),
+                Some(old_platforms) => {
+                    let only_in =
+                        |a: &'a BTreeMap<Platform, Properties>,
+                         b: &BTreeMap<Platform, Properties>| {
+                            let platforms: Vec<_> = a
+                                .keys()
+                                .filter(|platform| !b.contains_key(platform))
+                                .collect();
+                            (!platforms.is_empty()).then_some(VersionChange {
+                                version: *version,
+                                platforms,
+                            })
+                        };
+                    changes
+                        .added_drivers
+                        .extend(only_in(platforms, old_platforms));
+                    changes
+                        .removed_drivers
+                        .extend(only_in(old_platforms, platforms));
+
+                    for (platform, properties) in platforms {
+                        let Some(old_properties) = old_platforms.get(platform) else {
+                            continue;
+                        };
+
+                        let (changed, filled) = changed_fields(old_properties, properties);
+                        for (drivers, fields) in [
+                            (&mut changes.changed_in_place, changed),
+                            (&mut changes.filled_in, filled),
+                        ] {
+                            if !fields.is_empty() {
+                                drivers.push(ChangedDriver {
+                                    version: *version,
+                                    platform,
+                                    changes: fields,
+                                });
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        for (version, platforms) in &old.0 {
+            if !new.0.contains_key(version) {
+                changes.removed.push(VersionChange {
+                    version: *version,
+                    platforms: platforms.keys().collect(),
+                });
+            }
+        }
+
+        let old_platforms: BTreeSet<_> = old.0.values().flat_map(BTreeMap::keys).collect();
+        let new_platforms: BTreeSet<_> = new.0.values().flat_map(BTreeMap::keys).collect();
+        changes.new_platforms = new_platforms.difference(&old_platforms).copied().collect();
+
+        changes
+    }
+
+    /// Whether the snapshots have the same drivers, with the same properties.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.added_drivers.is_empty()
+            && self.removed_drivers.is_empty()
+            && self.new_platforms.is_empty()
+            && self.changed_in_place.is_empty()
+            && self.filled_in.is_empty()
+    }
+}
+
+/// The properties that have different values in both snapshots, and the ones that only the new
+/// snapshot has a value for. A value that the new snapshot lost isn't reported.
+fn changed_fields(old: &Properties, new: &Properties) -> (Vec<FieldChange>, Vec<FieldChange>) {
+    fn text(value: Option<impl ToString>) -> Option<String> {
+        value.map(|value| value.to_string())
+    }
+
+    let fields = [
+        (
+            "etag",
+            Some(&old.etag).filter(|etag| !etag.is_empty()).cloned(),
+            Some(&new.etag).filter(|etag| !etag.is_empty()).cloned(),
+        ),
+        ("md5", text(old.md5.as_ref()), text(new.md5.as_ref())),
+        (
+            "contentLength",
+            text(old.content_length),
+            text(new.content_length),
+        ),
+    ];
+
+    let mut changed = Vec::new();
+    let mut filled = Vec::new();
+    for (field, old, new) in fields {
+        match (&old, &new) {
+            (Some(a), Some(b)) if a != b => changed.push(FieldChange { field, old, new }),
+            (None, Some(_)) => filled.push(FieldChange { field, old, new }),
+            _ => {}
+        }
+    }
+
+    (changed, filled)
+}
+
+impl fmt::Display for Changes<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "no changes");
+        }
+
+        let platforms = |platforms: &[&Platform]| {
+            platforms
+                .iter()
+                .map(|platform| platform.name())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+
+        for (sign, versions) in [("+", &self.added), ("-", &self.removed)] {
+            for change in versions {
+                writeln!(
+                    f,
+                    "{} {} ({})",
+                    sign,
+                    change.version,
+                    platforms(&change.platforms)
+                )?;
+            }
+        }
+
+        for (sign, versions) in [("+", &self.added_drivers), ("-", &self.removed_drivers)] {
+            for change in versions {
+                writeln!(
+                    f,
+                    "{} {} drivers: {}",
+                    sign,
+                    change.version,
+                    platforms(&change.platforms)
+                )?;
+            }
+        }
+
+        if !self.new_platforms.is_empty() {
+            writeln!(f, "new platforms: {}", platforms(&self.new_platforms))?;
+        }
+
+        for driver in &self.changed_in_place {
+            let changes: Vec<_> = driver
+                .changes
+                .iter()
+                .map(|change| {
+                    format!(
+                        "{} {} -> {}",
+                        change.field,
+                        change.old.as_deref().unwrap_or("(none)"),
+                        change.new.as_deref().unwrap_or("(none)")
+                    )
+                })
+                .collect();
+            writeln!(
+                f,
+                "! {} {} changed in place: {}",
+                driver.version,
+                driver.platform,
+                changes.join(", ")
+            )?;
+        }
+
+        for driver in &self.filled_in {
+            let changes: Vec<_> = driver
+                .changes
+                .iter()
+                .map(|change| {
+                    format!(
+                        "{} {}",
+                        change.field,
+                        change.new.as_deref().unwrap_or_default()
+                    )
+                })
+                .collect();
+            writeln!(
+                f,
+                "~ {} {} filled in: {}",
+                driver.version,
+                driver.platform,
+                changes.join(", ")
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Load a snapshot: either a published `dist` directory, or a listing like `manifest.xml`.
+///
+/// Drivers that a `dist` only kept around after they were removed upstream are left out, so they
+/// show up as removed.
+pub fn load_snapshot(path: &Path) -> Result<Output> {
+    let mut output = if path.is_dir() {
+        let versions = path.join("versions");
+        ensure!(
+            versions.is_dir(),
+            "{} is not a published dist, it has no versions directory",
+            path.display()
+        );
+        merge::load_versions(&versions)?
+    } else {
+        let listing = Source::File(path.into())
+            .load(&RetryPolicy::default(), None)?
+            .ok_or_else(|| anyhow!("{} is not a listing", path.display()))?;
+
+        classify_blobs(listing.results.blobs.blobs, false).output
+    };
+
+    for platforms in output.0.values_mut() {
+        platforms.retain(|_, properties| properties.removed_upstream_at.is_none());
+    }
+    output.0.retain(|_, platforms| !platforms.is_empty());
+
+    Ok(output)
+}
+
+#[test]
+fn changes_between_snapshots() {
+    let properties = |etag: &str, length: u64| Properties {
+        etag: etag.into(),
+        content_length: Some(length),
+        ..Default::default()
+    };
+    let old = Output(BTreeMap::from([
+        (
+            "99.0.1150.2".parse().unwrap(),
+            BTreeMap::from([(Platform::Win64, properties("0x1", 1))]),
+        ),
+        (
+            "100.0.1154.0".parse().unwrap(),
+            BTreeMap::from([
+                (Platform::Win64, properties("0x2", 2)),
+                (Platform::Win32, properties("0x6", 6)),
+            ]),
+        ),
+    ]));
+    let new = Output(BTreeMap::from([
+        (
+            "100.0.1154.0".parse().unwrap(),
+            BTreeMap::from([
+                (
+                    Platform::Win64,
+                    // a new MD5 is filled in, a lost length isn't reported
+                    Properties {
+                        md5: Some(crate::properties::Base64(vec![0; 16])),
+                        content_length: None,
+                        ..properties("0x3", 2)
+                    },
+                ),
+                (Platform::Arm64, properties("0x7", 7)),
+            ]),
+        ),
+        (
+            "101.0.1160.0".parse().unwrap(),
+            BTreeMap::from([
+                (Platform::Win64, properties("0x4", 4)),
+                (Platform::Linux64, properties("0x5", 5)),
+            ]),
+        ),
+    ]));
+
+    let changes = Changes::new(&old, &new);
+    assert_eq!(
+        serde_json::to_value(&changes).unwrap(),
+        serde_json::json!({
+            "added": [{ "version": "101.0.1160.0", "platforms": ["linux64", "win64"] }],
+            "removed": [{ "version": "99.0.1150.2", "platforms": ["win64"] }],
+            "addedDrivers": [{ "version": "100.0.1154.0", "platforms": ["arm64"] }],
+            "removedDrivers": [{ "version": "100.0.1154.0", "platforms": ["win32"] }],
+            "newPlatforms": ["arm64", "linux64"],
+            "changedInPlace": [{
+                "version": "100.0.1154.0",
+                "platform": "win64",
+                "changes": [{ "field": "etag", "old": "0x2", "new": "0x3" }],
+            }],
+            "filledIn": [{
+                "version": "100.0.1154.0",
+                "platform": "win64",
+                "changes": [{ "field": "md5", "old": null, "new": "AAAAAAAAAAAAAAAAAAAAAA==" }],
+            }],
+        })
+    );
+    assert_eq!(
+        changes.to_string(),
+        "+ 101.0.1160.0 (linux64, win64)\n\
+         - 99.0.1150.2 (win64)\n\
+         + 100.0.1154.0 drivers: arm64\n\
+         - 100.0.1154.0 drivers: win32\n\
+         new platforms: arm64, linux64\n\
+         ! 100.0.1154.0 win64 changed in place: etag 0x2 -> 0x3\n\
+         ~ 100.0.1154.0 win64 filled in: md5 AAAAAAAAAAAAAAAAAAAAAA==\n"
+    );
+    assert_eq!(Changes::new(&new, &new).to_string(), "no changes\n");
+}
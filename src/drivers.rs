@@ -0,0 +1,461 @@
+//! A per-user cache of installed drivers, so each one is only downloaded once across runs.
+
+use std::{
+    fs::{metadata, read_dir, read_to_string, remove_dir, remove_dir_all},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    client::{Client, Resolved},
+    platform::Platform,
+    properties::Hex,
+    timestamp::Timestamp,
+    version::Version,
+    write_json,
+};
+
+/// The environment variable overriding the location of the per-user cache.
+pub const CACHE_DIR_ENV: &str = "MSEDGEDRIVER_CACHE_DIR";
+/// The file describing a cached driver, next to its executable.
+const ENTRY: &str = "entry.json";
+
+/// A driver in the cache, as described by its `entry.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedDriver {
+    /// The version of the driver.
+    pub version: Version,
+    /// The platform of the driver.
+    pub platform: Platform,
+    /// The `ETag` of the archive the driver was extracted from.
+    pub etag: String,
+    /// The MD5 of the archive the driver was extracted from, if the published cache listed it.
+    #[serde(rename = "md5Hex", default, skip_serializing_if = "Option::is_none")]
+    pub md5_hex: Option<Hex>,
+    /// The size of the executable, in bytes.
+    pub size: u64,
+    /// When the driver was last installed or reused.
+    #[serde(rename = "lastUsed")]
+    pub last_used: Timestamp,
+    /// The file name of the executable.
+    pub executable: String,
+    /// Where the executable is.
+    #[serde(skip)]
+    pub path: PathBuf,
+}
+
+/// Which drivers [`DriverCache::prune`] removes. Every limit that is set applies.
+#[derive(Debug, Default)]
+pub struct Prune {
+    /// Keep at most this many of the most recently used drivers.
+    pub keep: Option<usize>,
+    /// Remove drivers that weren't used for this long.
+    pub older_than: Option<Duration>,
+    /// Remove the least recently used drivers until the cache is at most this many bytes.
+    pub max_size: Option<u64>,
+}
+
+/// Extracted drivers, stored as `<version>/<platform>/<etag>/`.
+///
+/// A driver is reused as long as the published cache still lists the same `ETag` (and MD5) for it,
+/// so a driver that was replaced upstream is downloaded again.
+pub struct DriverCache {
+    root: PathBuf,
+}
+
+impl DriverCache {
+    /// The cache in the `root` directory.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// The per-user cache: `$MSEDGEDRIVER_CACHE_DIR` if it's set, otherwise `msedgedriver` in
+    /// `$XDG_CACHE_HOME`, `~/.cache` or, on Windows, `%LOCALAPPDATA%`.
+    pub fn user(var: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        let var = |name| {
+            var(name)
+                .filter(|value| !value.is_empty())
+                .map(PathBuf::from)
+        };
+        if let Some(root) = var(CACHE_DIR_ENV) {
+            return Ok(Self::new(root));
+        }
+
+        var("XDG_CACHE_HOME")
+            .or_else(|| var("HOME").map(|home| home.join(".cache")))
+            .or_else(|| var("LOCALAPPDATA"))
+            .map(|cache| Self::new(cache.join("msedgedriver")))
+            .ok_or_else(|| {
+                anyhow!(
+                    "unable to find a cache directory, set {} to choose one",
+                    CACHE_DIR_ENV
+                )
+            })
+    }
+
+    /// The directory the cache is in.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn directory(&self, version: Version, platform: &Platform, etag: &str) -> PathBuf {
+        // ETags are opaque, so only keep the characters that are safe in a file name
+        let etag: String = etag
+            .chars()
+            .map(|c| match c {
+                'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => c,
+                _ => '_',
+            })
+            .collect();
+
+        self.root
+            .join(version.to_string())
+            .join(platform.name())
+            .join(if etag.is_empty() { "_".into() } else { etag })
+    }
+
+    /// The executable of `driver` for `platform`, installing it with `client` unless a valid copy
+    /// is already cached.
+    pub fn install(
+        &self,
+        client: &Client,
+        driver: &Resolved,
+        platform: &Platform,
+    ) -> Result<PathBuf> {
+        if let Some(path) = self.get(driver, platform) {
+            return Ok(path);
+        }
+
+        let properties = &driver.properties;
+        let directory = self.directory(driver.version, platform, &properties.etag);
+        if directory.exists() {
+            // a leftover of an interrupted install
+            remove_dir_all(&directory)?;
+        }
+
+        let installed = client
+            .download(driver, platform, &directory)
+            .and_then(|path| {
+                let entry = CachedDriver {
+                    version: driver.version,
+                    platform: platform.clone(),
+                    etag: properties.etag.clone(),
+                    md5_hex: properties.md5_hex.clone(),
+                    size: metadata(&path)?.len(),
+                    last_used: Timestamp::now(),
+                    executable: file_name(&path)?,
+                    path,
+                };
+                write_json(&directory.join(ENTRY), &entry)?;
+                Ok(entry.path)
+            });
+        if installed.is_err() {
+            let _ = remove_dir_all(&directory);
+        }
+        installed
+    }
+
+    /// The cached executable of `driver`, if it was extracted from the archive the published cache
+    /// lists now. Marks it as used.
+    pub fn get(&self, driver: &Resolved, platform: &Platform) -> Option<PathBuf> {
+        let properties = &driver.properties;
+        let directory = self.directory(driver.version, platform, &properties.etag);
+        let mut entry = load_entry(&directory).ok()?;
+
+        let valid = entry.etag == properties.etag
+            && (entry.md5_hex.is_none()
+                || properties.md5_hex.is_none()
+                || entry.md5_hex == properties.md5_hex)
+            && entry.path.is_file();
+        if !valid {
+            return None;
+        }
+
+        entry.last_used = Timestamp::now();
+        // failing to record the use only makes the driver more likely to be pruned
+        let _ = write_json(&directory.join(ENTRY), &entry);
+        Some(entry.path)
+    }
+
+    /// Every cached driver, from the most to the least recently used.
+    ///
+    /// Cache entries that can't be read are skipped and described in `warnings`.
+    pub fn list(&self, warnings: &mut Vec<String>) -> Result<Vec<CachedDriver>> {
+        let mut drivers = Vec::new();
+        for version in subdirectories(&self.root)? {
+            for platform in subdirectories(&version)? {
+                for directory in subdirectories(&platform)? {
+                    match load_entry(&directory) {
+                        Ok(entry) => drivers.push(entry),
+                        Err(e) => {
+                            warnings.push(format!("skipping {}: {:#}", directory.display(), e))
+                        }
+                    }
+                }
+            }
+        }
+
+        drivers.sort_by(|a, b| {
+            (b.last_used, b.version, &b.platform).cmp(&(a.last_used, a.version, &a.platform))
+        });
+        Ok(drivers)
+    }
+
+    /// Remove the drivers that `prune` selects, returning them. Like with [`list`](Self::list),
+    /// entries that can't be read are described in `warnings` and left alone.
+    pub fn prune(&self, prune: &Prune, warnings: &mut Vec<String>) -> Result<Vec<CachedDriver>> {
+        let cutoff = prune
+            .older_than
+            .map(|age| Timestamp::now().saturating_sub(age));
+
+        let mut size = 0;
+        let mut removed = Vec::new();
+        for (i, driver) in self.list(warnings)?.into_iter().enumerate() {
+            size += driver.size;
+            let remove = cutoff.is_some_and(|cutoff| driver.last_used < cutoff)
+                || prune.keep.is_some_and(|keep| i >= keep)
+                || prune.max_size.is_some_and(|max_size| size > max_size);
+
+            if remove {
+                size -= driver.size;
+                self.remove(&driver)?;
+                removed.push(driver);
+            }
+        }
+
+        Ok(removed)
+    }
+
+    fn remove(&self, driver: &CachedDriver) -> Result<()> {
+        let directory = self.directory(driver.version, &driver.platform, &driver.etag);
+        remove_dir_all(&directory)
+            .with_context(|| format!("failed to remove {}", directory.display()))?;
+
+        // drop the platform and version directories once they're empty
+        for parent in directory.ancestors().skip(1).take(2) {
+            if remove_dir(parent).is_err() {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn load_entry(directory: &Path) -> Result<CachedDriver> {
+    let mut entry: CachedDriver = serde_json::from_str(&read_to_string(directory.join(ENTRY))?)?;
+    entry.path = directory.join(&entry.executable);
+    Ok(entry)
+}
+
+fn subdirectories(path: &Path) -> Result<Vec<PathBuf>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut directories = Vec::new();
+    for entry in read_dir(path)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            directories.push(entry.path());
+        }
+    }
+    Ok(directories)
+}
+
+fn file_name(path: &Path) -> Result<String> {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .map(Into::into)
+        .ok_or_else(|| anyhow!("invalid executable path {}", path.display()))
+}
+
+/// Parse an age like `30d`, `12h`, `45m` or `10s`.
+pub fn parse_age(s: &str) -> Result<Duration> {
+    let error = || anyhow!("invalid age {:?}, expected e.g. 30d or 12h", s);
+
+    let unit = match s.chars().last().ok_or_else(error)? {
+        'd' => 86_400,
+        'h' => 3600,
+        'm' => 60,
+        's' => 1,
+        _ => return Err(error()),
+    };
+    let count: u64 = s[..s.len() - 1].parse().map_err(|_| error())?;
+    count
+        .checked_mul(unit)
+        .map(Duration::from_secs)
+        .ok_or_else(|| anyhow!("age {:?} is too long", s))
+}
+
+/// Parse a size in bytes, optionally with a `K`, `M` or `G` suffix (powers of 1024).
+pub fn parse_size(s: &str) -> Result<u64> {
+    let error = || anyhow!("invalid size {:?}, expected e.g. 500M or 2G", s);
+
+    let (count, unit) = match s.char_indices().last().ok_or_else(error)? {
+        (i, 'K' | 'k') => (&s[..i], 1 << 10),
+        (i, 'M' | 'm') => (&s[..i], 1 << 20),
+        (i, 'G' | 'g') => (&s[..i], 1 << 30),
+        _ => (s, 1),
+    };
+    let count: u64 = count.parse().map_err(|_| error())?;
+    count
+        .checked_mul(unit)
+        .ok_or_else(|| anyhow!("size {:?} is too large", s))
+}
+
+#[test]
+fn ages_and_sizes_parse() {
+    assert_eq!(parse_age("30d").unwrap(), Duration::from_secs(30 * 86_400));
+    assert_eq!(parse_age("12h").unwrap(), Duration::from_secs(12 * 3600));
+    assert!(parse_age("30").is_err());
+    assert!(parse_age("d").is_err());
+    assert!(parse_age("999999999999999999d").is_err());
+
+    assert_eq!(parse_size("1024").unwrap(), 1024);
+    assert_eq!(parse_size("500M").unwrap(), 500 << 20);
+    assert_eq!(parse_size("2g").unwrap(), 2 << 30);
+    assert!(parse_size("2T").is_err());
+}
+
+#[test]
+fn drivers_are_reused_until_their_etag_changes() {
+    use crate::{
+        client::{driver_archive, Location},
+        retry::RetryPolicy,
+        stub::{Reply, StubServer},
+        Properties,
+    };
+
+    let archive = driver_archive();
+    let server = StubServer::new(vec![
+        Reply::new(200, archive.clone()),
+        Reply::new(200, archive.clone()),
+    ]);
+    let tmp = tempfile::tempdir().unwrap();
+    let cache = DriverCache::new(tmp.path());
+    let client = Client::new(Location::Dir(tmp.path().into()), RetryPolicy::default());
+
+    let driver = |etag: &str| Resolved {
+        version: "100.0.1154.0".parse().unwrap(),
+        properties: Properties {
+            url: server.url("/edgedriver_linux64.zip"),
+            etag: etag.into(),
+            content_length: Some(archive.len() as u64),
+            ..Default::default()
+        },
+    };
+
+    let path = cache
+        .install(&client, &driver("0x1"), &Platform::Linux64)
+        .unwrap();
+    assert_eq!(
+        path,
+        tmp.path()
+            .join("100.0.1154.0")
+            .join("linux64")
+            .join("0x1")
+            .join("msedgedriver")
+    );
+    let again = cache
+        .install(&client, &driver("0x1"), &Platform::Linux64)
+        .unwrap();
+    assert_eq!(again, path);
+    assert_eq!(server.requests().len(), 1);
+
+    assert_eq!(cache.get(&driver("0x2"), &Platform::Linux64), None);
+    cache
+        .install(&client, &driver("0x2"), &Platform::Linux64)
+        .unwrap();
+    assert_eq!(server.requests().len(), 2);
+
+    let drivers = cache.list(&mut Vec::new()).unwrap();
+    assert_eq!(drivers.len(), 2);
+    assert_eq!(drivers[0].size, "#!/bin/sh\n".len() as u64);
+}
+
+#[test]
+fn prune_removes_the_least_recently_used_drivers() {
+    use std::fs::{create_dir_all, write};
+
+    let tmp = tempfile::tempdir().unwrap();
+    let cache = DriverCache::new(tmp.path());
+
+    let now = Timestamp::now();
+    for (version, days, size) in [
+        ("100.0.1154.0", 1, 10),
+        ("101.0.1160.0", 2, 20),
+        ("102.0.1185.0", 10, 30),
+        ("103.0.1264.0", 20, 40),
+    ] {
+        let version: Version = version.parse().unwrap();
+        let directory = cache.directory(version, &Platform::Win64, "0x1");
+        create_dir_all(&directory).unwrap();
+        write(directory.join("msedgedriver.exe"), "").unwrap();
+        let entry = CachedDriver {
+            version,
+            platform: Platform::Win64,
+            etag: "0x1".into(),
+            md5_hex: None,
+            size,
+            last_used: now.saturating_sub(Duration::from_secs(days * 86_400)),
+            executable: "msedgedriver.exe".into(),
+            path: PathBuf::new(),
+        };
+        write_json(&directory.join(ENTRY), &entry).unwrap();
+    }
+
+    let versions = |drivers: Vec<CachedDriver>| -> Vec<_> {
+        drivers
+            .iter()
+            .map(|driver| driver.version.to_string())
+            .collect()
+    };
+
+    let removed = cache
+        .prune(
+            &Prune {
+                older_than: Some(parse_age("15d").unwrap()),
+                ..Default::default()
+            },
+            &mut Vec::new(),
+        )
+        .unwrap();
+    assert_eq!(versions(removed), ["103.0.1264.0"]);
+    assert!(!tmp.path().join("103.0.1264.0").exists());
+
+    let removed = cache
+        .prune(
+            &Prune {
+                max_size: Some(35),
+                ..Default::default()
+            },
+            &mut Vec::new(),
+        )
+        .unwrap();
+    assert_eq!(versions(removed), ["102.0.1185.0"]);
+
+    let removed = cache
+        .prune(
+            &Prune {
+                keep: Some(1),
+                ..Default::default()
+            },
+            &mut Vec::new(),
+        )
+        .unwrap();
+    assert_eq!(versions(removed), ["101.0.1160.0"]);
+
+    // an entry that can't be read is skipped, not removed
+    let broken = cache.directory("104.0.1293.0".parse().unwrap(), &Platform::Win64, "0x1");
+    create_dir_all(&broken).unwrap();
+    let mut warnings = Vec::new();
+    assert_eq!(
+        versions(cache.list(&mut warnings).unwrap()),
+        ["100.0.1154.0"]
+    );
+    assert_eq!(warnings.len(), 1);
+    assert!(warnings[0].starts_with(&format!("skipping {}", broken.display())));
+}
@@ -0,0 +1,12 @@
+//! Test data shared by the tests of several modules.
+
+use crate::Properties;
+
+/// The properties of a driver downloaded from `url`, removed upstream if `removed`.
+pub fn driver(url: &str, removed: bool) -> Properties {
+    Properties {
+        url: url.into(),
+        removed_upstream_at: removed.then(|| "2022-06-01T00:00:00Z".parse().unwrap()),
+        ..Default::default()
+    }
+}
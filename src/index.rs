@@ -0,0 +1,157 @@
+//! The indexes of the output: `index.json`, and `platforms/<platform>.json` for each platform.
+
+use std::{cmp::Reverse, collections::BTreeMap};
+
+use serde::Serialize;
+
+use crate::{platform::Platform, version::Version, Output, Properties};
+
+/// Every cached version, as published in `index.json`.
+#[derive(Debug, Serialize)]
+pub struct Index<'a> {
+    /// Oldest first.
+    pub versions: Vec<IndexEntry<'a>>,
+}
+
+/// A version in `index.json`.
+#[derive(Debug, Serialize)]
+pub struct IndexEntry<'a> {
+    /// The cached version.
+    pub version: Version,
+    /// The platforms this version has a driver for, sorted by name.
+    pub platforms: Vec<&'a Platform>,
+    /// The path of the version file, relative to the root of the output.
+    pub file: String,
+}
+
+impl<'a> Index<'a> {
+    /// Build the index of `output`, sorted from the oldest to the newest version.
+    pub fn new(output: &'a Output) -> Self {
+        let versions = output
+            .0
+            .iter()
+            .map(|(version, platforms)| IndexEntry {
+                version: *version,
+                platforms: platforms.keys().collect(),
+                file: version_file(version),
+            })
+            .collect();
+
+        Self { versions }
+    }
+}
+
+/// Every version with a driver for a single platform, as published in `platforms/<platform>.json`.
+#[derive(Debug, Serialize)]
+pub struct PlatformIndex<'a> {
+    /// The platform every version has a driver for.
+    pub platform: &'a Platform,
+    /// Newest first.
+    pub versions: Vec<PlatformVersion<'a>>,
+}
+
+/// A version in `platforms/<platform>.json`.
+#[derive(Debug, Serialize)]
+pub struct PlatformVersion<'a> {
+    /// The version of the driver.
+    pub version: Version,
+    /// The properties of the driver, flattened into the same object.
+    #[serde(flatten)]
+    pub properties: &'a Properties,
+}
+
+/// Transpose `output` into an index per platform.
+pub fn platform_indexes(output: &Output) -> BTreeMap<&Platform, PlatformIndex<'_>> {
+    let mut indexes: BTreeMap<_, PlatformIndex> = BTreeMap::new();
+    for (version, platforms) in &output.0 {
+        for (platform, properties) in platforms {
+            indexes
+                .entry(platform)
+                .or_insert_with(|| PlatformIndex {
+                    platform,
+                    versions: Vec::new(),
+                })
+                .versions
+                .push(PlatformVersion {
+                    version: *version,
+                    properties,
+                });
+        }
+    }
+
+    for index in indexes.values_mut() {
+        index.versions.sort_by_key(|entry| Reverse(entry.version));
+    }
+
+    indexes
+}
+
+/// The path of the file holding the properties of `version`, relative to the root of the output.
+pub fn version_file(version: &Version) -> String {
+    format!("versions/{}.json", version)
+}
+
+#[test]
+fn index_is_sorted() {
+    let output = Output(BTreeMap::from([
+        (
+            "100.0.1154.0".parse().unwrap(),
+            BTreeMap::from([
+                (Platform::Win64, Default::default()),
+                (Platform::Arm64, Default::default()),
+            ]),
+        ),
+        (
+            "99.0.1150.2".parse().unwrap(),
+            BTreeMap::from([(Platform::Linux64, Default::default())]),
+        ),
+    ]));
+
+    assert_eq!(
+        serde_json::to_value(Index::new(&output)).unwrap(),
+        serde_json::json!({
+            "versions": [
+                {
+                    "version": "99.0.1150.2",
+                    "platforms": ["linux64"],
+                    "file": "versions/99.0.1150.2.json",
+                },
+                {
+                    "version": "100.0.1154.0",
+                    "platforms": ["arm64", "win64"],
+                    "file": "versions/100.0.1154.0.json",
+                },
+            ]
+        })
+    );
+}
+
+#[test]
+fn platform_index_is_newest_first() {
+    let properties = |url: &str| Properties {
+        url: url.into(),
+        ..Default::default()
+    };
+    let output = Output(BTreeMap::from([
+        (
+            "99.0.1150.2".parse().unwrap(),
+            BTreeMap::from([(Platform::Linux64, properties("99-linux64"))]),
+        ),
+        (
+            "100.0.1154.0".parse().unwrap(),
+            BTreeMap::from([
+                (Platform::Linux64, properties("100-linux64")),
+                (Platform::Win64, properties("100-win64")),
+            ]),
+        ),
+    ]));
+
+    let indexes = platform_indexes(&output);
+    let linux = serde_json::to_value(&indexes[&Platform::Linux64]).unwrap();
+
+    assert_eq!(linux["platform"], "linux64");
+    assert_eq!(linux["versions"][0]["version"], "100.0.1154.0");
+    assert_eq!(linux["versions"][0]["url"], "100-linux64");
+    assert_eq!(linux["versions"][1]["version"], "99.0.1150.2");
+    assert_eq!(indexes[&Platform::Win64].versions.len(), 1);
+}
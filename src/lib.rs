@@ -0,0 +1,707 @@
+//! A cache of the msedgedriver container listing, which seems to go missing a lot.
+//!
+//! The listing is loaded from a [`Source`] into a [`Listing`], every blob is classified by
+//! [`classify()`], and the drivers are collected into an [`Output`] that [`build`] writes as a
+//! static site. [`update`] wraps it all up the way the command line tool runs it: only rebuilding
+//! when the listing changed, and [`publish`]ing the result atomically.
+
+#![warn(missing_docs)]
+
+use std::{
+    collections::BTreeMap,
+    fs::{create_dir, remove_dir_all, rename, write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{ensure, Result};
+use serde::{Deserialize, Serialize};
+
+use cft::{KnownGoodVersions, LastKnownGoodVersions};
+use channels::Pointer;
+use classify::{classify, BlobKind, SkippedBlob, Strictness};
+use index::Index;
+use platform::Platform;
+use properties::Properties;
+use retry::RetryPolicy;
+use source::{Listing, Origin, Source};
+use timestamp::Timestamp;
+use verify::Cache;
+use version::Version;
+
+pub mod aliases;
+pub mod browser;
+pub mod cft;
+pub mod channels;
+pub mod classify;
+pub mod client;
+pub mod diff;
+pub mod drivers;
+#[cfg(test)]
+mod fixture;
+pub mod index;
+pub mod links;
+pub mod merge;
+pub mod platform;
+pub mod properties;
+pub mod retry;
+pub mod serve;
+pub mod source;
+#[cfg(test)]
+mod stub;
+pub mod timestamp;
+pub mod verify;
+pub mod version;
+
+/// The container that is listed by default.
+pub const MANIFEST_URL: &str = "https://msedgedriver.azureedge.net";
+/// The `User-Agent` of every request.
+pub const USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), " ", env!("CARGO_PKG_VERSION"));
+/// The directory the output is published to by the command line tool.
+pub const DIST: &str = "dist";
+
+/// The drivers of every version, by platform. This is what the output files are written from.
+#[derive(Debug, Default)]
+pub struct Output(pub BTreeMap<Version, BTreeMap<Platform, Properties>>);
+
+/// A page of the container listing (or all of them merged), as returned by Azure's List Blobs.
+#[derive(Debug, Default, Deserialize)]
+pub struct EnumerationResults {
+    /// The blobs of the page.
+    #[serde(rename = "Blobs", default)]
+    pub blobs: Blobs,
+    /// Where the next page starts, empty on the last page.
+    #[serde(rename = "NextMarker", default)]
+    pub next_marker: String,
+}
+
+/// The `<Blobs>` element of a listing page.
+#[derive(Debug, Default, Deserialize)]
+pub struct Blobs {
+    /// Every blob, in the order they were listed.
+    #[serde(rename = "Blob", default)]
+    pub blobs: Vec<Blob>,
+}
+
+/// A single file in the container.
+#[derive(Debug, Default, Deserialize)]
+pub struct Blob {
+    /// The path of the blob in the container, like `100.0.1154.0/edgedriver_win64.zip`.
+    #[serde(rename = "Name", default)]
+    pub name: String,
+    /// Where the blob can be downloaded from.
+    #[serde(rename = "Url", default)]
+    pub url: String,
+    /// The properties of the blob.
+    #[serde(rename = "Properties", default)]
+    pub properties: BlobProperties,
+}
+
+/// The properties of a blob, exactly as listed.
+#[derive(Debug, Default, Deserialize)]
+pub struct BlobProperties {
+    /// An HTTP date, like `Tue, 15 Mar 2022 00:26:40 GMT`.
+    #[serde(rename = "Last-Modified", default)]
+    pub last_modified: String,
+    /// The `ETag`, without quotes.
+    #[serde(rename = "Etag", default)]
+    pub etag: String,
+    /// The size of the blob, in bytes.
+    #[serde(rename = "Content-Length", default)]
+    pub content_length: String,
+    /// The MIME type of the blob.
+    #[serde(rename = "Content-Type", default)]
+    pub content_type: String,
+    /// The base64 encoded MD5 of the blob, empty if upstream didn't compute one.
+    #[serde(rename = "Content-MD5", default)]
+    pub content_md5: String,
+}
+
+/// Options that affect how the output is built from a listing.
+#[derive(Debug, Default)]
+pub struct BuildOptions {
+    /// How to react to blobs that are not driver archives.
+    pub strictness: Strictness,
+    /// Fetch the `LATEST_*` pointer blobs and publish them as `channels.json`. This needs the
+    /// network, so it's off for builds from a saved listing.
+    pub resolve_channels: bool,
+    /// Publish the upstream strings of the parsed properties in their place, for consumers of the
+    /// old format.
+    pub raw_properties: bool,
+    /// Download every driver into this cache directory to verify it and compute its SHA-2 digests.
+    pub verify: Option<PathBuf>,
+    /// How failed requests, for the listing, the pointers and the drivers, are retried.
+    pub retry: RetryPolicy,
+}
+
+/// Options of [`update`].
+#[derive(Debug, Default)]
+pub struct UpdateOptions {
+    /// Merge into the previously published versions instead of replacing them, so versions that
+    /// were pruned upstream stay in the cache.
+    pub incremental: bool,
+    /// Rebuild even if the listing didn't change since the last run.
+    pub force: bool,
+    /// How the output is built from the listing.
+    pub build: BuildOptions,
+}
+
+/// How a successful [`update`] ended.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// The output was built and published.
+    Done {
+        /// The problems the build ran into, but didn't fail on (see [`build`]).
+        warnings: Vec<String>,
+    },
+    /// The listing didn't change since the last run, so nothing was rebuilt.
+    Unchanged,
+}
+
+/// Load the listing from `source` and publish the output built from it to `dist`, unless it
+/// didn't change since the listing that `dist` was built from.
+pub fn update(dist: &Path, source: &Source, options: &UpdateOptions) -> Result<Outcome> {
+    let last_origin = if options.force {
+        None
+    } else {
+        Origin::load(&dist.join("source.json"))
+    };
+    let listing = match source.load(&options.build.retry, last_origin.as_ref())? {
+        Some(listing) => listing,
+        None => return Ok(Outcome::Unchanged),
+    };
+
+    let previous = if options.incremental {
+        Some(merge::load_versions(&dist.join("versions"))?)
+    } else {
+        None
+    };
+
+    let mut warnings = Vec::new();
+    publish(dist, |out| {
+        warnings = build(out, listing, previous, &options.build)?;
+        Ok(())
+    })?;
+    Ok(Outcome::Done { warnings })
+}
+
+/// The blobs of a listing, sorted by what they turned out to be.
+#[derive(Debug, Default)]
+pub struct Classified {
+    /// The driver archives.
+    pub output: Output,
+    /// The `LATEST_*` pointers, still to be resolved.
+    pub pointers: Vec<(Pointer, Blob)>,
+    /// Everything else.
+    pub skipped: Vec<SkippedBlob>,
+    /// A description of every driver property that didn't parse.
+    pub malformed: Vec<String>,
+    /// A description of every driver for a platform that isn't known yet.
+    pub unknown_platforms: Vec<String>,
+}
+
+/// Classify every blob of a listing, collecting the drivers into an [`Output`].
+///
+/// With `raw_properties`, the drivers keep the unparsed upstream properties too.
+pub fn classify_blobs(blobs: Vec<Blob>, raw_properties: bool) -> Classified {
+    let mut classified = Classified::default();
+    for blob in blobs {
+        match classify(&blob.name) {
+            Ok(BlobKind::Driver(version, platform)) => {
+                if !platform.is_known() {
+                    classified
+                        .unknown_platforms
+                        .push(format!("unknown platform {} in {}", platform, blob.name));
+                }
+                let properties =
+                    Properties::from_blob(blob, raw_properties, &mut classified.malformed);
+                let version = classified.output.0.entry(version).or_default();
+                version.insert(platform, properties);
+            }
+            Ok(BlobKind::Pointer(pointer)) => classified.pointers.push((pointer, blob)),
+            Err(reason) => classified.skipped.push(SkippedBlob {
+                name: blob.name,
+                url: blob.url,
+                reason,
+            }),
+        }
+    }
+
+    classified
+}
+
+/// Write the raw listing and a JSON file per version into the (empty) `out` directory.
+///
+/// When a `previous` output is given, the listing is merged on top of it instead of replacing it.
+/// Returns the problems with the listing that are only warned about at the configured strictness,
+/// and anything else that was left out of the output without failing the build.
+pub fn build(
+    out: &Path,
+    listing: Listing,
+    previous: Option<Output>,
+    options: &BuildOptions,
+) -> Result<Vec<String>> {
+    // simple sanity check to make sure there *was* any results
+    let blobs = &listing.results.blobs.blobs;
+    ensure!(
+        blobs.len() > 1,
+        "expected the listing to contain multiple blobs, found {}",
+        blobs.len()
+    );
+
+    let versions = out.join("versions");
+    let pages = out.join("pages");
+    create_dir(&versions)?;
+    create_dir(&pages)?;
+
+    for (i, page) in listing.pages.iter().enumerate() {
+        write(pages.join(format!("{}.xml", i)), page.as_bytes())?;
+    }
+
+    write(out.join("manifest.xml"), listing.manifest()?.as_bytes())?;
+
+    write_json(&out.join("source.json"), &listing.origin)?;
+
+    let Classified {
+        mut output,
+        pointers,
+        mut skipped,
+        malformed,
+        unknown_platforms,
+    } = classify_blobs(listing.results.blobs.blobs, options.raw_properties);
+
+    let mut warnings = listing.warnings;
+    let channels = if options.resolve_channels {
+        let (channels, unresolved) = channels::resolve(pointers, &options.retry, &mut warnings);
+        skipped.extend(unresolved);
+
+        write_json(&out.join("channels.json"), &channels)?;
+        Some(channels)
+    } else {
+        if !pointers.is_empty() {
+            warnings.push(format!(
+                "not resolving {} LATEST_* pointer(s) without the network, skipping channels.json",
+                pointers.len()
+            ));
+        }
+        None
+    };
+
+    write_json(&out.join("skipped.json"), &skipped)?;
+    let strictness = options.strictness;
+    strictness.check(&skipped, &mut warnings)?;
+    strictness.check_problems("malformed blob properties", &malformed, &mut warnings)?;
+    strictness.check_problems(
+        "drivers for unknown platforms",
+        &unknown_platforms,
+        &mut warnings,
+    )?;
+
+    if let Some(cache) = &options.verify {
+        let failed = verify::verify(&mut output, &Cache::new(cache), &options.retry);
+        strictness.check_problems("drivers that failed verification", &failed, &mut warnings)?;
+    }
+
+    let mut output = match previous {
+        Some(previous) => merge::merge(previous, output, Timestamp::now()),
+        None => output,
+    };
+    // drivers kept from the previous output are published in the current format too
+    for properties in output.0.values_mut().flat_map(BTreeMap::values_mut) {
+        if !options.raw_properties {
+            properties.raw = None;
+        } else if properties.raw.is_none() {
+            properties.raw = Some(properties.listed());
+        }
+    }
+
+    let platforms: BTreeMap<_, _> = output
+        .0
+        .values()
+        .flat_map(BTreeMap::keys)
+        .map(|platform| (platform, platform.info()))
+        .collect();
+    write_json(&out.join("platforms.json"), &platforms)?;
+    write_json(&out.join("index.json"), &Index::new(&output))?;
+    aliases::write_aliases(out, &output)?;
+
+    let platforms_dir = out.join("platforms");
+    create_dir(&platforms_dir)?;
+    for (platform, index) in index::platform_indexes(&output) {
+        write_json(&platforms_dir.join(format!("{}.json", platform)), &index)?;
+    }
+
+    for (version, properties) in &output.0 {
+        write_json(&out.join(index::version_file(version)), properties)?;
+    }
+
+    let timestamp = cft::timestamp(&output);
+    write_json(
+        &out.join("known-good-versions-with-downloads.json"),
+        &KnownGoodVersions::new(&output, timestamp),
+    )?;
+    if let Some(channels) = &channels {
+        write_json(
+            &out.join("last-known-good-versions.json"),
+            &LastKnownGoodVersions::new(channels, timestamp),
+        )?;
+    }
+
+    Ok(warnings)
+}
+
+/// Write `value` as pretty printed JSON, ending with a newline.
+///
+/// Every map that ends up in the output is a `BTreeMap`, so the result only depends on `value`.
+pub fn write_json(path: &Path, value: &impl Serialize) -> Result<()> {
+    let mut content = serde_json::to_string_pretty(value)?;
+    content.push('\n');
+    write(path, content.as_bytes())?;
+    Ok(())
+}
+
+/// Run `build` against a staging directory next to `dist`, and only swap it into place once the
+/// whole build succeeded, so a failed fetch or parse leaves the last known good output untouched.
+pub fn publish(dist: &Path, build: impl FnOnce(&Path) -> Result<()>) -> Result<()> {
+    let staging = sibling_directory(dist, "staging");
+    let previous = sibling_directory(dist, "previous");
+
+    // a crashed run may have left either of these behind
+    for leftover in [&staging, &previous] {
+        if leftover.exists() {
+            remove_dir_all(leftover)?;
+        }
+    }
+
+    create_dir(&staging)?;
+    if let Err(e) = build(&staging) {
+        remove_dir_all(&staging)?;
+        return Err(e);
+    }
+
+    if dist.exists() {
+        rename(dist, &previous)?;
+    }
+
+    if let Err(e) = rename(&staging, dist) {
+        if previous.exists() {
+            rename(&previous, dist)?;
+        }
+        return Err(e.into());
+    }
+
+    if previous.exists() {
+        remove_dir_all(&previous)?;
+    }
+
+    Ok(())
+}
+
+/// A hidden directory next to `dist`, so renames between the two never cross filesystems.
+fn sibling_directory(dist: &Path, suffix: &str) -> PathBuf {
+    let name = dist
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or(DIST.into());
+
+    dist.with_file_name(format!(".{}.{}", name, suffix))
+}
+
+#[test]
+fn failed_build_keeps_previous_dist() {
+    let tmp = tempfile::tempdir().unwrap();
+    let dist = tmp.path().join("dist");
+
+    publish(&dist, |out| Ok(write(out.join("manifest.xml"), "good")?)).unwrap();
+    assert!(publish(&dist, |out| {
+        write(out.join("manifest.xml"), "partial")?;
+        anyhow::bail!("upstream went missing")
+    })
+    .is_err());
+
+    assert_eq!(
+        std::fs::read_to_string(dist.join("manifest.xml")).unwrap(),
+        "good"
+    );
+    assert_eq!(std::fs::read_dir(tmp.path()).unwrap().count(), 1);
+}
+
+#[test]
+fn successful_build_replaces_dist() {
+    let tmp = tempfile::tempdir().unwrap();
+    let dist = tmp.path().join("dist");
+
+    publish(&dist, |out| Ok(write(out.join("old.txt"), "old")?)).unwrap();
+    publish(&dist, |out| Ok(write(out.join("new.txt"), "new")?)).unwrap();
+
+    assert!(!dist.join("old.txt").exists());
+    assert!(dist.join("new.txt").exists());
+    assert_eq!(std::fs::read_dir(tmp.path()).unwrap().count(), 1);
+}
+
+#[cfg(test)]
+fn read_json(path: &Path) -> serde_json::Value {
+    serde_json::from_slice(&std::fs::read(path).unwrap()).unwrap()
+}
+
+#[test]
+fn build_from_saved_manifest() {
+    let tmp = tempfile::tempdir().unwrap();
+    let saved = tmp.path().join("manifest.xml");
+    write(
+        &saved,
+        "<EnumerationResults><Blobs>\
+         <Blob><Name>100.0.1154.0/edgedriver_arm64.zip</Name></Blob>\
+         <Blob><Name>100.0.1154.0/edgedriver_win64.zip</Name></Blob>\
+         <Blob><Name>100.0.1154.0/credits.html</Name></Blob>\
+         </Blobs><NextMarker /></EnumerationResults>",
+    )
+    .unwrap();
+
+    let out = tmp.path().join("out");
+    create_dir(&out).unwrap();
+    let listing = Source::File(saved.clone())
+        .load(&RetryPolicy::default(), None)
+        .unwrap()
+        .unwrap();
+    build(&out, listing, None, &BuildOptions::default()).unwrap();
+
+    assert_eq!(
+        std::fs::read(out.join("manifest.xml")).unwrap(),
+        std::fs::read(saved).unwrap()
+    );
+    assert!(out.join("versions").join("100.0.1154.0.json").exists());
+
+    let index = read_json(&out.join("index.json"));
+    assert_eq!(
+        index["versions"][0]["platforms"],
+        serde_json::json!(["arm64", "win64"])
+    );
+
+    let platforms = read_json(&out.join("platforms.json"));
+    assert_eq!(
+        platforms["arm64"],
+        serde_json::json!({ "os": "windows", "arch": "arm64", "known": true })
+    );
+
+    let skipped = read_json(&out.join("skipped.json"));
+    assert_eq!(skipped[0]["name"], "100.0.1154.0/credits.html");
+    assert_eq!(skipped[0]["reason"], "notADriverArchive");
+}
+
+#[test]
+fn unknown_platforms_are_collected() {
+    let blob = |name: &str| Blob {
+        name: name.into(),
+        ..Default::default()
+    };
+    let classified = classify_blobs(
+        vec![
+            blob("100.0.1154.0/edgedriver_win64.zip"),
+            blob("101.0.1160.0/edgedriver_riscv64.zip"),
+        ],
+        false,
+    );
+
+    assert_eq!(classified.output.0.len(), 2);
+    assert_eq!(
+        classified.unknown_platforms,
+        ["unknown platform riscv64 in 101.0.1160.0/edgedriver_riscv64.zip"]
+    );
+    assert!(Strictness::Deny
+        .check_problems(
+            "drivers for unknown platforms",
+            &classified.unknown_platforms,
+            &mut Vec::new()
+        )
+        .is_err());
+}
+
+#[test]
+fn builds_are_byte_identical() {
+    fn files(dir: &Path, root: &Path, found: &mut Vec<(PathBuf, Vec<u8>)>) {
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                files(&path, root, found);
+            } else {
+                let content = std::fs::read(&path).unwrap();
+                found.push((path.strip_prefix(root).unwrap().into(), content));
+            }
+        }
+        found.sort();
+    }
+
+    let tmp = tempfile::tempdir().unwrap();
+    let saved = tmp.path().join("manifest.xml");
+    let blob = |name: &str, modified: &str| {
+        format!(
+            "<Blob><Name>{}</Name><Url>https://example.com/{}</Url><Properties>\
+             <Last-Modified>{}</Last-Modified><Etag>0x1</Etag><Content-Length>1</Content-Length>\
+             </Properties></Blob>",
+            name, name, modified
+        )
+    };
+    let blobs: String = [
+        (
+            "99.0.1150.2/edgedriver_win64.zip",
+            "Tue, 01 Mar 2022 10:00:00 GMT",
+        ),
+        (
+            "100.0.1154.0/edgedriver_arm64.zip",
+            "Tue, 15 Mar 2022 00:26:40 GMT",
+        ),
+        (
+            "100.0.1154.0/edgedriver_linux64.zip",
+            "Tue, 15 Mar 2022 00:20:00 GMT",
+        ),
+        (
+            "100.0.1154.0/edgedriver_mac64.zip",
+            "Tue, 15 Mar 2022 00:21:00 GMT",
+        ),
+        (
+            "100.0.1154.0/edgedriver_mac64_m1.zip",
+            "Tue, 15 Mar 2022 00:22:00 GMT",
+        ),
+        (
+            "100.0.1154.0/edgedriver_win32.zip",
+            "Tue, 15 Mar 2022 00:23:00 GMT",
+        ),
+        (
+            "100.0.1154.0/edgedriver_win64.zip",
+            "Tue, 15 Mar 2022 00:24:00 GMT",
+        ),
+        (
+            "101.0.1160.0/edgedriver_riscv64.zip",
+            "Tue, 12 Apr 2022 00:24:00 GMT",
+        ),
+    ]
+    .iter()
+    .map(|(name, modified)| blob(name, modified))
+    .collect();
+    write(
+        &saved,
+        format!(
+            "<EnumerationResults><Blobs>{}</Blobs><NextMarker /></EnumerationResults>",
+            blobs
+        ),
+    )
+    .unwrap();
+
+    let mut builds = Vec::new();
+    for name in ["a", "b"] {
+        let out = tmp.path().join(name);
+        create_dir(&out).unwrap();
+        let listing = Source::File(saved.clone())
+            .load(&RetryPolicy::default(), None)
+            .unwrap()
+            .unwrap();
+        build(&out, listing, None, &BuildOptions::default()).unwrap();
+
+        let mut found = Vec::new();
+        files(&out, &out, &mut found);
+        builds.push(found);
+    }
+
+    assert!(builds[0].len() > 10);
+    assert_eq!(builds[0], builds[1]);
+    assert!(builds[0]
+        .iter()
+        .filter(|(path, _)| path.extension() == Some("json".as_ref()))
+        .all(|(_, content)| content.ends_with(b"\n")));
+
+    let known_good = read_json(
+        &tmp.path()
+            .join("a")
+            .join("known-good-versions-with-downloads.json"),
+    );
+    assert_eq!(known_good["timestamp"], "2022-04-12T00:24:00Z");
+
+    let version = read_json(
+        &tmp.path()
+            .join("a")
+            .join("versions")
+            .join("99.0.1150.2.json"),
+    );
+    assert_eq!(version["win64"]["lastModified"], "2022-03-01T10:00:00Z");
+    assert_eq!(version["win64"]["contentLength"], 1);
+}
+
+#[test]
+fn raw_properties_apply_to_kept_drivers() {
+    let tmp = tempfile::tempdir().unwrap();
+    let saved = tmp.path().join("manifest.xml");
+    let build_from = |out: &str, version: &str, previous, raw_properties| {
+        write(
+            &saved,
+            format!(
+                "<EnumerationResults><Blobs>\
+                 <Blob><Name>{0}/edgedriver_win64.zip</Name><Properties>\
+                 <Last-Modified>Tue, 15 Mar 2022 00:26:40 GMT</Last-Modified>\
+                 <Content-Length>1</Content-Length></Properties></Blob>\
+                 <Blob><Name>{0}/edgedriver_arm64.zip</Name></Blob>\
+                 </Blobs><NextMarker /></EnumerationResults>",
+                version
+            ),
+        )
+        .unwrap();
+        let out = tmp.path().join(out);
+        create_dir(&out).unwrap();
+        let listing = Source::File(saved.clone())
+            .load(&RetryPolicy::default(), None)
+            .unwrap()
+            .unwrap();
+        let options = BuildOptions {
+            raw_properties,
+            ..Default::default()
+        };
+        build(&out, listing, previous, &options).unwrap();
+        out.join("versions")
+    };
+
+    let typed = build_from("typed", "100.0.1154.0", None, false);
+    let previous = merge::load_versions(&typed).unwrap();
+    let raw = build_from("raw", "101.0.1160.0", Some(previous), true);
+
+    for version in ["100.0.1154.0", "101.0.1160.0"] {
+        let win64 = &read_json(&raw.join(format!("{}.json", version)))["win64"];
+        assert_eq!(win64["lastModified"], "Tue, 15 Mar 2022 00:26:40 GMT");
+        assert_eq!(win64["contentLength"], "1");
+        assert_eq!(win64["md5"], "");
+    }
+
+    let previous = merge::load_versions(&raw).unwrap();
+    let typed = build_from("typed-again", "102.0.1245.0", Some(previous), false);
+    let win64 = &read_json(&typed.join("101.0.1160.0.json"))["win64"];
+    assert_eq!(win64["lastModified"], "2022-03-15T00:26:40Z");
+    assert_eq!(win64["contentLength"], 1);
+}
+
+#[test]
+fn unchanged_listing_keeps_dist() {
+    use stub::{Reply, StubServer};
+
+    let tmp = tempfile::tempdir().unwrap();
+    let server = StubServer::new(vec![
+        Reply::new(
+            200,
+            "<EnumerationResults><Blobs>\
+             <Blob><Name>100.0.1154.0/edgedriver_arm64.zip</Name></Blob>\
+             <Blob><Name>100.0.1154.0/edgedriver_win64.zip</Name></Blob>\
+             </Blobs><NextMarker /></EnumerationResults>",
+        )
+        .header("ETag", "\"0x1\""),
+        Reply::new(304, ""),
+    ]);
+    let source = Source::Network(vec![server.url("/")]);
+    let dist = tmp.path().join("dist");
+
+    let run = || update(&dist, &source, &UpdateOptions::default()).unwrap();
+    assert_eq!(run(), Outcome::Done { warnings: vec![] });
+    assert_eq!(run(), Outcome::Unchanged);
+
+    assert!(tmp.path().join("dist").join("index.json").exists());
+    assert_eq!(
+        read_json(&tmp.path().join("dist").join("source.json"))["etag"],
+        "\"0x1\""
+    );
+}
@@ -0,0 +1,261 @@
+//! Checking that the published download URLs still point at the drivers we cached.
+
+use std::{
+    path::Path,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+};
+
+use anyhow::{ensure, Result};
+use serde::Serialize;
+
+use crate::{
+    merge, platform::Platform, retry::RetryPolicy, version::Version, write_json, Output,
+    Properties, USER_AGENT,
+};
+
+/// The result of checking every URL, as published in `link-health.json`.
+#[derive(Debug, Serialize)]
+pub struct LinkHealth<'a> {
+    /// The number of links that were checked.
+    pub checked: usize,
+    /// The number of links that are not `ok`.
+    pub drifted: usize,
+    /// Every link, sorted by version and platform.
+    pub links: Vec<Link<'a>>,
+}
+
+/// A checked link, in `link-health.json`.
+#[derive(Debug, Serialize)]
+pub struct Link<'a> {
+    /// The version of the driver.
+    pub version: Version,
+    /// The platform of the driver.
+    pub platform: &'a Platform,
+    /// The published download URL of the driver.
+    pub url: &'a str,
+    /// Whether the URL still serves the cached driver.
+    pub status: LinkStatus,
+    /// The HTTP status code of the response, if there was one.
+    #[serde(rename = "httpStatus", skip_serializing_if = "Option::is_none")]
+    pub http_status: Option<u16>,
+    /// The current `ETag`, when it differs from the cached one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+    /// The current `Content-Length`, when it differs from the cached one.
+    #[serde(rename = "contentLength", skip_serializing_if = "Option::is_none")]
+    pub content_length: Option<u64>,
+    /// Why the link couldn't be checked, for `error` links.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// How a link compares with the cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LinkStatus {
+    /// The URL still serves the cached blob.
+    Ok,
+    /// The URL is gone (`404` or `410`).
+    Missing,
+    /// The URL serves a blob with another `ETag`.
+    EtagChanged,
+    /// The URL serves a blob with another `Content-Length`.
+    SizeChanged,
+    /// Any other failure, e.g. a server error or a connection failure.
+    Error,
+}
+
+impl<'a> LinkHealth<'a> {
+    /// Send a `HEAD` request for every driver in `output`, with at most `concurrency` requests in
+    /// flight at once.
+    ///
+    /// Drivers that were removed upstream are left out, since their links are known to be gone.
+    pub fn check(output: &'a Output, concurrency: usize, policy: &RetryPolicy) -> Self {
+        let targets: Vec<_> = output
+            .0
+            .iter()
+            .flat_map(|(version, platforms)| {
+                platforms
+                    .iter()
+                    .filter(|(_, properties)| properties.removed_upstream_at.is_none())
+                    .map(move |(platform, properties)| (*version, platform, properties))
+            })
+            .collect();
+
+        let next = AtomicUsize::new(0);
+        let links = Mutex::new((0..targets.len()).map(|_| None).collect::<Vec<_>>());
+        thread::scope(|scope| {
+            for _ in 0..concurrency.clamp(1, targets.len().max(1)) {
+                scope.spawn(|| loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(&(version, platform, properties)) = targets.get(i) else {
+                        break;
+                    };
+
+                    let link = check_link(version, platform, properties, policy);
+                    links.lock().unwrap()[i] = Some(link);
+                });
+            }
+        });
+
+        let links: Vec<_> = links.into_inner().unwrap().into_iter().flatten().collect();
+        Self {
+            checked: links.len(),
+            drifted: links
+                .iter()
+                .filter(|link| link.status != LinkStatus::Ok)
+                .count(),
+            links,
+        }
+    }
+}
+
+/// How many links [`check_links`] checked, and how many of them drifted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct LinkSummary {
+    /// The number of links that were checked.
+    pub checked: usize,
+    /// The number of links that are not `ok`.
+    pub drifted: usize,
+}
+
+/// Check every URL in the published `dist/versions` and write the result to `link-health.json`.
+pub fn check_links(dist: &Path, concurrency: usize, policy: &RetryPolicy) -> Result<LinkSummary> {
+    let output = merge::load_versions(&dist.join("versions"))?;
+    ensure!(
+        !output.0.is_empty(),
+        "no published versions to check in {}",
+        dist.display()
+    );
+
+    let health = LinkHealth::check(&output, concurrency, policy);
+    write_json(&dist.join("link-health.json"), &health)?;
+
+    Ok(LinkSummary {
+        checked: health.checked,
+        drifted: health.drifted,
+    })
+}
+
+fn check_link<'a>(
+    version: Version,
+    platform: &'a Platform,
+    properties: &'a Properties,
+    policy: &RetryPolicy,
+) -> Link<'a> {
+    let mut link = Link {
+        version,
+        platform,
+        url: &properties.url,
+        status: LinkStatus::Ok,
+        http_status: None,
+        etag: None,
+        content_length: None,
+        error: None,
+    };
+
+    let response = policy.run(&properties.url, || {
+        Ok(ureq::head(&properties.url)
+            .set("User-Agent", USER_AGENT)
+            .call()?)
+    });
+    let response = match response {
+        Ok(response) => response,
+        Err(e) => {
+            match e.downcast_ref::<ureq::Error>() {
+                Some(ureq::Error::Status(status @ (404 | 410), _)) => {
+                    link.status = LinkStatus::Missing;
+                    link.http_status = Some(*status);
+                }
+                Some(ureq::Error::Status(status, _)) => {
+                    link.status = LinkStatus::Error;
+                    link.http_status = Some(*status);
+                    link.error = Some(e.to_string());
+                }
+                _ => {
+                    link.status = LinkStatus::Error;
+                    link.error = Some(e.to_string());
+                }
+            }
+            return link;
+        }
+    };
+    link.http_status = Some(response.status());
+
+    // the listing has bare ETags, while the header is quoted (and possibly weak)
+    let etag = response
+        .header("ETag")
+        .map(|etag| etag.trim_start_matches("W/").trim_matches('"'));
+    let content_length = response
+        .header("Content-Length")
+        .and_then(|length| length.parse().ok());
+
+    if etag.is_some_and(|etag| !properties.etag.is_empty() && etag != properties.etag) {
+        link.status = LinkStatus::EtagChanged;
+        link.etag = etag.map(Into::into);
+    }
+    if properties
+        .content_length
+        .is_some_and(|length| content_length.is_some_and(|current| current != length))
+    {
+        if link.status == LinkStatus::Ok {
+            link.status = LinkStatus::SizeChanged;
+        }
+        link.content_length = content_length;
+    }
+
+    link
+}
+
+#[test]
+fn links_are_compared_with_the_cache() {
+    use crate::stub::{Reply, StubServer};
+    use std::collections::BTreeMap;
+
+    let server = StubServer::new(vec![
+        Reply::new(200, "driver").header("ETag", "\"0x1\""),
+        Reply::new(200, "driver").header("ETag", "\"0x2\""),
+        Reply::new(200, "drivers").header("ETag", "\"0x3\""),
+        Reply::new(404, ""),
+    ]);
+    let properties = |path: &str, etag: &str, removed: bool| Properties {
+        etag: etag.into(),
+        content_length: Some(6),
+        ..crate::fixture::driver(&server.url(path), removed)
+    };
+    let output = Output(BTreeMap::from([(
+        "100.0.1154.0".parse().unwrap(),
+        BTreeMap::from([
+            (Platform::Arm64, properties("/arm64.zip", "0x1", false)),
+            (Platform::Linux64, properties("/linux64.zip", "0x1", false)),
+            (Platform::Mac64, properties("/mac64.zip", "0x3", false)),
+            (Platform::Win64, properties("/win64.zip", "0x4", false)),
+            (Platform::Win32, properties("/win32.zip", "0x5", true)),
+        ]),
+    )]));
+
+    let policy = RetryPolicy {
+        max_attempts: 1,
+        ..Default::default()
+    };
+    let health = LinkHealth::check(&output, 1, &policy);
+    let statuses: Vec<_> = health.links.iter().map(|link| link.status).collect();
+
+    assert_eq!(
+        statuses,
+        [
+            LinkStatus::Ok,
+            LinkStatus::EtagChanged,
+            LinkStatus::SizeChanged,
+            LinkStatus::Missing
+        ]
+    );
+    assert_eq!(health.links[1].etag.as_deref(), Some("0x2"));
+    assert_eq!(health.links[2].content_length, Some(7));
+    assert_eq!((health.checked, health.drifted), (4, 3));
+    assert_eq!(server.requests().len(), 4);
+}
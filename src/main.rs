@@ -1,180 +1,885 @@
 use std::{
-    collections::HashMap,
     env,
-    fs::{create_dir, remove_dir_all, write},
-    io::Error as IoError,
-    path::{Path, PathBuf},
+    fmt::{Display, Write as _},
+    net::TcpListener,
+    path::PathBuf,
     process::exit,
+    str::FromStr,
+    time::Duration,
 };
 
-use anyhow::Result;
-use quick_xml::de::from_str;
-use serde::{Deserialize, Serialize};
+use anyhow::{anyhow, bail, ensure, Error, Result};
+use msedgedriver_manifest_cache::{
+    browser::{installed_version, parse_browser_version, DEFAULT_COMMAND},
+    client::{Client, Location, Request},
+    diff::{load_snapshot, Changes},
+    drivers::{parse_age, parse_size, DriverCache, Prune},
+    links::check_links,
+    merge::load_versions,
+    platform::Platform,
+    retry::RetryPolicy,
+    serve::serve,
+    source::Source,
+    update,
+    verify::{verify, Cache},
+    version::Version,
+    write_json, Outcome, UpdateOptions, DIST, MANIFEST_URL,
+};
+use serde::Serialize;
+
+use config::Config;
+
+mod config;
+
+const VERIFY_CACHE: &str = ".cache/verify";
+const LINK_CHECK_CONCURRENCY: usize = 8;
+const SERVE_ADDRESS: &str = "127.0.0.1:8080";
+
+/// The exit code of invalid command line arguments.
+const USAGE_EXIT_CODE: i32 = 2;
+/// The exit code of a run that found the listing unchanged and left `dist` as it was.
+const UNCHANGED_EXIT_CODE: i32 = 3;
+/// The exit code of a check (`verify`, `check-links`) that ran, but found problems.
+const PROBLEMS_EXIT_CODE: i32 = 4;
+
+const USAGE: &str = "\
+usage: msedgedriver-manifest-cache [<global options>] [<command>] [<options>]
+
+commands:
+  build        fetch the listing and publish the cache to --out (the default command)
+  fetch        print the listing, with all of its pages spliced together
+  verify       download every published driver and check its size and MD5
+  diff         compare two snapshots: diff <old> <new> [--json <path>]
+  query        resolve a driver: query <version|major|channel|installed> [--platform <name>]
+               [--browser <version>] [--edge-command <command>]
+  install      install a driver into the per-user cache: install <request> [<query options>]
+               [--dir <path>]
+  serve        serve --out over HTTP: serve [--addr <host:port>]
+  check-links  check that the published URLs still serve the cached drivers
+  cache        manage the per-user driver cache: cache list|prune
+
+global options:
+  --out <dir>            the published cache, ./dist by default
+  --source <url|path|->  where build and fetch read the listing from, or the published cache
+                         query and install resolve against
+  --format text|json     how command output is printed, not supported by build, fetch and serve
+  --quiet                only print command output, warnings and errors
+  --verbose              also print what is being done
+  --retries <n>          retry failed requests up to n times, 4 by default
+  --retry-base-delay <ms>
+                         the delay before the first retry, doubled after every one, 500 by default
+  --retry-max-delay <ms> the longest delay between two retries, 30000 by default
+
+exit codes: 0 success, 1 failure, 2 invalid arguments, 3 listing unchanged (build),
+4 problems found (verify, check-links)
+";
+
+/// How command output is printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Text,
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => bail!("unknown format {:?}, expected text or json", s),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Verbosity {
+    Quiet,
+    Normal,
+    Verbose,
+}
 
-const MANIFEST_URL: &str = "https://msedgedriver.azureedge.net";
-const USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), " ", env!("CARGO_PKG_VERSION"));
-const DIST: &str = "dist";
+/// Command line options shared by every command.
+#[derive(Debug)]
+struct Global {
+    /// The published cache.
+    out: PathBuf,
+    /// Where the listing, or for `query` and `install` the published cache, is read from.
+    source: Option<String>,
+    format: Format,
+    verbosity: Verbosity,
+    /// How failed requests are retried, by every command that fetches something.
+    retry: RetryPolicy,
+}
 
-#[derive(Debug, Serialize, Hash, PartialEq, Eq)]
-#[serde(transparent)]
-struct Version(String);
+impl Global {
+    /// Take the global options out of `args`, wherever they are, and return the remaining ones.
+    fn parse(args: impl IntoIterator<Item = String>) -> Result<(Self, Vec<String>)> {
+        let mut global = Self {
+            out: DIST.into(),
+            source: None,
+            format: Format::Text,
+            verbosity: Verbosity::Normal,
+            retry: RetryPolicy {
+                on_retry: |message| warn(message),
+                ..Default::default()
+            },
+        };
+        let mut rest = Vec::new();
 
-#[derive(Debug, Serialize, Hash, PartialEq, Eq)]
-#[serde(transparent)]
-struct Platform(String);
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| anyhow!("{} expects a value", arg))
+            };
+            match arg.as_str() {
+                "--out" => global.out = value()?.into(),
+                "--source" => global.source = Some(value()?),
+                "--format" => global.format = value()?.parse()?,
+                "--quiet" => global.verbosity = Verbosity::Quiet,
+                "--verbose" => global.verbosity = Verbosity::Verbose,
+                "--retries" => {
+                    global.retry.max_attempts = value()?.parse::<u32>()?.saturating_add(1)
+                }
+                "--retry-base-delay" => global.retry.base_delay = parse_millis(&value()?)?,
+                "--retry-max-delay" => global.retry.max_delay = parse_millis(&value()?)?,
+                _ => rest.push(arg),
+            }
+        }
+
+        Ok((global, rest))
+    }
 
+    /// Print a status message, unless `--quiet`.
+    fn info(&self, message: impl Display) {
+        if self.verbosity != Verbosity::Quiet {
+            println!("{}", message);
+        }
+    }
+
+    /// Print what is being done, with `--verbose`.
+    fn debug(&self, message: impl Display) {
+        if self.verbosity == Verbosity::Verbose {
+            eprintln!("{}", message);
+        }
+    }
+
+    /// Print the output of a command: `value` with `--format json`, `text` otherwise.
+    fn report(&self, value: &impl Serialize, text: impl Display) -> Result<()> {
+        match self.format {
+            Format::Text => print!("{}", text),
+            Format::Json => println!("{}", serde_json::to_string_pretty(value)?),
+        }
+        Ok(())
+    }
+}
+
+/// Where `build` and `fetch` read the listing from.
 #[derive(Debug, Default)]
-struct Output(HashMap<Version, HashMap<Platform, Properties>>);
+struct ListingOptions {
+    saved: Option<Source>,
+    config_path: Option<PathBuf>,
+    cli: Config,
+}
 
-#[derive(Debug, Default, Deserialize)]
-struct EnumerationResults {
-    #[serde(rename = "Blobs", default)]
-    blobs: Blobs,
+impl ListingOptions {
+    /// Start from the global `--source`, which is either a listing URL or a saved listing.
+    fn new(source: Option<&str>) -> Self {
+        let mut options = Self::default();
+        match source {
+            Some(url) if url.starts_with("http://") || url.starts_with("https://") => {
+                options.cli.url = Some(url.into())
+            }
+            Some(path) => options.saved = Some(saved_listing(path)),
+            None => {}
+        }
+        options
+    }
+
+    /// Handle `arg` if it's a listing option, returning whether it was one.
+    fn parse_arg(&mut self, arg: &str, value: impl FnOnce() -> Result<String>) -> Result<bool> {
+        match arg {
+            "--from-file" => self.saved = Some(saved_listing(&value()?)),
+            "--url" => self.cli.url = Some(value()?),
+            "--mirror" => self.cli.mirrors.push(value()?),
+            "--config" => self.config_path = Some(value()?.into()),
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    /// A saved listing, or the listing URLs from the command line, the environment and the config
+    /// file, in that order of precedence.
+    fn source(self) -> Result<Source> {
+        if let Some(saved) = self.saved {
+            return Ok(saved);
+        }
+
+        let file = match self
+            .config_path
+            .or_else(|| env::var_os(config::CONFIG_ENV).map(Into::into))
+        {
+            Some(path) => Config::load(&path)?,
+            None => Config::default(),
+        };
+        let env = Config::from_env(|name| env::var(name).ok());
+        Ok(Source::Network(
+            self.cli.or(env).or(file).urls(MANIFEST_URL),
+        ))
+    }
 }
 
-#[derive(Debug, Default, Deserialize)]
+/// Parse a delay given in milliseconds.
+fn parse_millis(s: &str) -> Result<Duration> {
+    s.parse()
+        .map(Duration::from_millis)
+        .map_err(|_| anyhow!("invalid delay {:?}, expected a number of milliseconds", s))
+}
 
-struct Blobs {
-    #[serde(rename = "Blob", default)]
-    blobs: Vec<Blob>,
+fn saved_listing(path: &str) -> Source {
+    match path {
+        "-" => Source::Stdin,
+        path => Source::File(path.into()),
+    }
+}
+
+/// Command line options of `diff`.
+#[derive(Debug)]
+struct DiffOptions {
+    old: PathBuf,
+    new: PathBuf,
+    /// Where `changes.json` is written.
+    json: PathBuf,
 }
 
-#[derive(Debug, Default, Deserialize)]
+impl DiffOptions {
+    fn parse(args: impl IntoIterator<Item = String>) -> Result<Self> {
+        let mut snapshots = Vec::new();
+        let mut json = PathBuf::from("changes.json");
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--json" => {
+                    json = args
+                        .next()
+                        .ok_or_else(|| anyhow!("{} expects a value", arg))?
+                        .into()
+                }
+                _ if arg.starts_with("--") => bail!("unknown argument: {}", arg),
+                _ => snapshots.push(PathBuf::from(arg)),
+            }
+        }
 
-struct Blob {
-    #[serde(rename = "Name", default)]
-    name: String,
-    #[serde(rename = "Url", default)]
-    url: String,
-    #[serde(rename = "Properties", default)]
-    properties: BlobProperties,
+        match <[PathBuf; 2]>::try_from(snapshots) {
+            Ok([old, new]) => Ok(Self { old, new, json }),
+            Err(_) => bail!("diff expects two snapshots: diff <old> <new> [--json <path>]"),
+        }
+    }
 }
 
-#[derive(Debug, Default, Deserialize)]
+/// Command line options of `query`.
+#[derive(Debug)]
+struct QueryOptions {
+    /// What to resolve, or `None` for the driver compatible with the installed Microsoft Edge.
+    request: Option<Request>,
+    platform: Platform,
+    /// The command Microsoft Edge is installed as, for `installed`.
+    edge_command: String,
+}
 
-struct BlobProperties {
-    #[serde(rename = "Last-Modified", default)]
-    last_modified: String,
-    #[serde(rename = "Etag", default)]
-    etag: String,
-    #[serde(rename = "Content-Length", default)]
-    content_length: String,
-    #[serde(rename = "Content-Type", default)]
-    content_type: String,
-    #[serde(rename = "Content-MD5", default)]
-    content_md5: String,
+impl QueryOptions {
+    fn parse(args: impl IntoIterator<Item = String>) -> Result<Self> {
+        let mut request = None;
+        let mut platform = None;
+        let mut edge_command = None;
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| anyhow!("{} expects a value", arg))
+            };
+            match arg.as_str() {
+                "--platform" => platform = Some(Platform::from_name(&value()?)),
+                "--edge-command" => edge_command = Some(value()?),
+                "--browser" => {
+                    ensure!(request.is_none(), "query expects a single request");
+                    let browser = parse_browser_version(&value()?)?;
+                    request = Some(Some(Request::Browser(browser)))
+                }
+                _ if arg.starts_with("--") => bail!("unknown argument: {}", arg),
+                _ if request.is_some() => bail!("query expects a single request"),
+                "installed" => request = Some(None),
+                _ => request = Some(Some(arg.parse()?)),
+            }
+        }
+
+        let request = request.ok_or_else(|| {
+            anyhow!("query expects a version, a major release, a channel or installed")
+        })?;
+        ensure!(
+            request.is_none() || edge_command.is_none(),
+            "--edge-command only applies to installed"
+        );
+
+        Ok(Self {
+            request,
+            platform: match platform.or_else(Platform::host) {
+                Some(platform) => platform,
+                None => bail!("no drivers are published for this platform, pass --platform"),
+            },
+            edge_command: edge_command.unwrap_or_else(|| DEFAULT_COMMAND.into()),
+        })
+    }
+}
+
+/// Command line options of `install`.
+#[derive(Debug)]
+struct InstallOptions {
+    /// The driver to install, given like for `query`.
+    query: QueryOptions,
+    /// The cache directory, instead of the per-user one.
+    dir: Option<PathBuf>,
+}
+
+impl InstallOptions {
+    fn parse(args: impl IntoIterator<Item = String>) -> Result<Self> {
+        let mut dir = None;
+        let mut rest = Vec::new();
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--dir" => {
+                    dir = Some(
+                        args.next()
+                            .ok_or_else(|| anyhow!("{} expects a value", arg))?
+                            .into(),
+                    )
+                }
+                _ => rest.push(arg),
+            }
+        }
+
+        Ok(Self {
+            query: QueryOptions::parse(rest)?,
+            dir,
+        })
+    }
 }
 
-#[derive(Debug, Default, Serialize)]
-struct Properties {
-    url: String,
-    #[serde(rename = "lastModified")]
-    last_modified: String,
-    etag: String,
-    md5: String,
-    #[serde(rename = "contentLength")]
-    content_length: String,
-    #[serde(rename = "contentType", default)]
-    content_type: String,
+/// Command line options of `cache`.
+#[derive(Debug)]
+struct CacheOptions {
+    /// The cache directory, instead of the per-user one.
+    dir: Option<PathBuf>,
+    /// What to remove, or `None` to list the cached drivers.
+    prune: Option<Prune>,
 }
 
-impl From<Blob> for Properties {
-    fn from(blob: Blob) -> Self {
-        Self {
-            url: blob.url,
-            last_modified: blob.properties.last_modified,
-            etag: blob.properties.etag,
-            md5: blob.properties.content_md5,
-            content_length: blob.properties.content_length,
-            content_type: blob.properties.content_type,
+impl CacheOptions {
+    fn parse(args: impl IntoIterator<Item = String>) -> Result<Self> {
+        let mut args = args.into_iter();
+        let mut prune = match args.next().as_deref() {
+            Some("list") => None,
+            Some("prune") => Some(Prune::default()),
+            _ => bail!("cache expects a command: cache list|prune [--dir <path>]"),
+        };
+        let mut dir = None;
+
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| anyhow!("{} expects a value", arg))
+            };
+            match (arg.as_str(), &mut prune) {
+                ("--dir", _) => dir = Some(value()?.into()),
+                ("--keep", Some(prune)) => prune.keep = Some(value()?.parse()?),
+                ("--older-than", Some(prune)) => prune.older_than = Some(parse_age(&value()?)?),
+                ("--max-size", Some(prune)) => prune.max_size = Some(parse_size(&value()?)?),
+                _ => bail!("unknown argument: {}", arg),
+            }
+        }
+
+        if let Some(prune) = &prune {
+            ensure!(
+                prune.keep.is_some() || prune.older_than.is_some() || prune.max_size.is_some(),
+                "prune expects --keep, --older-than or --max-size"
+            );
         }
+        Ok(Self { dir, prune })
     }
 }
 
+/// A command and its options.
+#[derive(Debug)]
+enum Command {
+    Build {
+        listing: ListingOptions,
+        update: UpdateOptions,
+    },
+    Fetch {
+        listing: ListingOptions,
+    },
+    Verify {
+        cache: PathBuf,
+    },
+    Diff(DiffOptions),
+    Query(QueryOptions),
+    Install(InstallOptions),
+    Serve {
+        address: String,
+    },
+    CheckLinks {
+        concurrency: usize,
+    },
+    Cache(CacheOptions),
+    Help,
+}
+
+impl Command {
+    fn parse(args: Vec<String>, global: &Global) -> Result<Self> {
+        if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+            return Ok(Self::Help);
+        }
+
+        let mut args = args.into_iter().peekable();
+        // options without a command are build options, like they were before there were commands
+        let command = match args.peek() {
+            Some(arg) if !arg.starts_with('-') => args.next(),
+            _ => None,
+        };
+
+        let name = command.as_deref().unwrap_or("build");
+        let command = match name {
+            "build" => Self::parse_build(args, global),
+            "fetch" => Self::parse_fetch(args, global),
+            "verify" => Self::parse_verify(args),
+            "diff" => DiffOptions::parse(args).map(Self::Diff),
+            "query" => QueryOptions::parse(args).map(Self::Query),
+            "install" => InstallOptions::parse(args).map(Self::Install),
+            "serve" => Self::parse_serve(args),
+            "check-links" => Self::parse_check_links(args),
+            "cache" => CacheOptions::parse(args).map(Self::Cache),
+            "help" => Ok(Self::Help),
+            command => bail!("unknown command: {}", command),
+        }?;
+
+        // these have no output that could be printed as JSON
+        let text_only = matches!(
+            command,
+            Self::Build { .. } | Self::Fetch { .. } | Self::Serve { .. }
+        );
+        ensure!(
+            global.format == Format::Text || !text_only,
+            "{} doesn't support --format json",
+            name
+        );
+        Ok(command)
+    }
+
+    fn parse_build(mut args: impl Iterator<Item = String>, global: &Global) -> Result<Self> {
+        let mut listing = ListingOptions::new(global.source.as_deref());
+        let mut update = UpdateOptions::default();
+
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| anyhow!("{} expects a value", arg))
+            };
+            if listing.parse_arg(&arg, &mut value)? {
+                continue;
+            }
+            match arg.as_str() {
+                "--incremental" => update.incremental = true,
+                "--force" => update.force = true,
+                "--strictness" => update.build.strictness = value()?.parse()?,
+                "--raw-properties" => update.build.raw_properties = true,
+                "--verify" => {
+                    update
+                        .build
+                        .verify
+                        .get_or_insert_with(|| VERIFY_CACHE.into());
+                }
+                "--verify-cache" => update.build.verify = Some(value()?.into()),
+                _ => bail!("unknown argument: {}", arg),
+            }
+        }
+
+        Ok(Self::Build { listing, update })
+    }
+
+    fn parse_fetch(mut args: impl Iterator<Item = String>, global: &Global) -> Result<Self> {
+        let mut listing = ListingOptions::new(global.source.as_deref());
+        while let Some(arg) = args.next() {
+            let value = || {
+                args.next()
+                    .ok_or_else(|| anyhow!("{} expects a value", arg))
+            };
+            if !listing.parse_arg(&arg, value)? {
+                bail!("unknown argument: {}", arg);
+            }
+        }
+
+        Ok(Self::Fetch { listing })
+    }
+
+    fn parse_verify(mut args: impl Iterator<Item = String>) -> Result<Self> {
+        let mut cache = PathBuf::from(VERIFY_CACHE);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--verify-cache" => {
+                    cache = args
+                        .next()
+                        .ok_or_else(|| anyhow!("{} expects a value", arg))?
+                        .into()
+                }
+                _ => bail!("unknown argument: {}", arg),
+            }
+        }
+
+        Ok(Self::Verify { cache })
+    }
+
+    fn parse_serve(mut args: impl Iterator<Item = String>) -> Result<Self> {
+        let mut address = SERVE_ADDRESS.to_owned();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--addr" => {
+                    address = args
+                        .next()
+                        .ok_or_else(|| anyhow!("{} expects a value", arg))?
+                }
+                _ => bail!("unknown argument: {}", arg),
+            }
+        }
+
+        Ok(Self::Serve { address })
+    }
+
+    fn parse_check_links(mut args: impl Iterator<Item = String>) -> Result<Self> {
+        let mut concurrency = LINK_CHECK_CONCURRENCY;
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--concurrency" => {
+                    concurrency = args
+                        .next()
+                        .ok_or_else(|| anyhow!("{} expects a value", arg))?
+                        .parse()?;
+                    ensure!(concurrency > 0, "--concurrency must be at least 1");
+                }
+                _ => bail!("unknown argument: {}", arg),
+            }
+        }
+
+        Ok(Self::CheckLinks { concurrency })
+    }
+}
+
+/// How a successful command ended, which decides the exit code.
+/// Print a warning, even with `--quiet`.
+fn warn(message: impl Display) {
+    eprintln!("warning: {}", message);
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Status {
+    Done,
+    /// `build` found the listing unchanged.
+    Unchanged,
+    /// A check ran, but found problems.
+    Problems,
+}
+
 fn main() {
-    if let Err(e) = run(env::current_dir()) {
-        eprintln!("fatal error: {}", e);
-        exit(1);
+    let parsed = Global::parse(env::args().skip(1))
+        .and_then(|(global, args)| Ok((Command::parse(args, &global)?, global)));
+    let (command, global) = match parsed {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, USAGE);
+            exit(USAGE_EXIT_CODE);
+        }
+    };
+
+    match run(&global, command) {
+        Ok(Status::Done) => {}
+        Ok(Status::Unchanged) => exit(UNCHANGED_EXIT_CODE),
+        Ok(Status::Problems) => exit(PROBLEMS_EXIT_CODE),
+        Err(e) => {
+            eprintln!("fatal error: {}", e);
+            exit(1);
+        }
     }
 }
 
-fn run(cwd: Result<PathBuf, IoError>) -> Result<()> {
-    let dist = cwd?.join(DIST);
-    let versions = dist.join("versions");
-    clean_dist_directory(&dist, &versions)?;
+fn run(global: &Global, command: Command) -> Result<Status> {
+    match command {
+        Command::Build {
+            listing,
+            mut update,
+        } => build(global, listing.source()?, &mut update),
+        Command::Fetch { listing } => fetch(global, &listing.source()?),
+        Command::Verify { cache } => verify_drivers(global, &Cache::new(cache)),
+        Command::Diff(options) => diff(global, &options),
+        Command::Query(options) => query(global, options),
+        Command::Install(options) => install(global, options),
+        Command::Serve { address } => {
+            ensure!(
+                global.out.is_dir(),
+                "{} doesn't exist, build it first",
+                global.out.display()
+            );
+            let listener = TcpListener::bind(&address)?;
+            // the address is the output of the command, so it's printed even with --quiet
+            println!(
+                "serving {} on http://{}",
+                global.out.display(),
+                listener.local_addr()?
+            );
+            serve(&listener, &global.out, |e| {
+                warn(format_args!("failed to answer a request: {}", e))
+            })?;
+            Ok(Status::Done)
+        }
+        Command::CheckLinks { concurrency } => {
+            let summary = check_links(&global.out, concurrency, &global.retry)?;
+            global.report(
+                &summary,
+                format_args!(
+                    "{} of {} links drifted, see {}\n",
+                    summary.drifted,
+                    summary.checked,
+                    global.out.join("link-health.json").display()
+                ),
+            )?;
+            Ok(problems_if(summary.drifted > 0))
+        }
+        Command::Cache(options) => cache(global, &options),
+        Command::Help => {
+            print!("{}", USAGE);
+            Ok(Status::Done)
+        }
+    }
+}
 
-    let manifest = fetch_manifest_from_network()?;
-    write(dist.join("manifest.xml"), manifest.as_bytes())?;
+fn problems_if(found: bool) -> Status {
+    if found {
+        Status::Problems
+    } else {
+        Status::Done
+    }
+}
 
-    let results: EnumerationResults = from_str(&manifest)?;
+/// Build the output from the listing and publish it to `--out`.
+fn build(global: &Global, source: Source, options: &mut UpdateOptions) -> Result<Status> {
+    // the LATEST_* pointers can only be resolved with the network
+    options.build.resolve_channels = matches!(source, Source::Network(_));
+    options.build.retry = global.retry.clone();
+    global.debug(format_args!(
+        "building {} from {:?}",
+        global.out.display(),
+        source
+    ));
 
-    // simple sanity check to make sure there *was* any results
-    assert!(results.blobs.blobs.len() > 1);
+    match update(&global.out, &source, options)? {
+        Outcome::Done { warnings } => {
+            warnings.iter().for_each(warn);
+            global.info(format_args!("published {}", global.out.display()));
+            Ok(Status::Done)
+        }
+        Outcome::Unchanged => {
+            global.info(format_args!(
+                "the listing didn't change since the last run, kept {}",
+                global.out.display()
+            ));
+            Ok(Status::Unchanged)
+        }
+    }
+}
 
-    let output = results
-        .blobs
-        .blobs
-        .into_iter()
-        .fold(Output::default(), |mut acc, blob| {
-            let (version, platform) = parse_version_and_platform(&blob.name).unwrap();
-            let version = acc.0.entry(version).or_default();
-            version.insert(platform, Properties::from(blob));
+/// Print the whole listing, e.g. to build from it later with `--source <path>`.
+fn fetch(global: &Global, source: &Source) -> Result<Status> {
+    let listing = source
+        .load(&global.retry, None)?
+        .ok_or_else(|| anyhow!("the listing couldn't be loaded"))?;
+    listing.warnings.iter().for_each(warn);
+    global.debug(format_args!(
+        "read {} page(s) with {} blobs from {:?}",
+        listing.pages.len(),
+        listing.results.blobs.blobs.len(),
+        listing.origin
+    ));
 
-            acc
-        });
+    print!("{}", listing.manifest()?);
+    Ok(Status::Done)
+}
 
-    for (version, properties) in output.0 {
-        let content = serde_json::to_string_pretty(&properties)?;
-        write(
-            versions.join(format!("{}.json", version.0)),
-            content.as_bytes(),
-        )?;
+/// The output of `verify`.
+#[derive(Debug, Serialize)]
+struct VerifyReport {
+    checked: usize,
+    /// A description of each driver that failed verification.
+    failed: Vec<String>,
+}
+
+/// Verify every published driver that can still be downloaded.
+fn verify_drivers(global: &Global, cache: &Cache) -> Result<Status> {
+    let mut output = load_versions(&global.out.join("versions"))?;
+    let checked = output
+        .0
+        .values()
+        .flat_map(|platforms| platforms.values())
+        .filter(|properties| properties.removed_upstream_at.is_none())
+        .count();
+    global.debug(format_args!("verifying {} drivers", checked));
+
+    let report = VerifyReport {
+        checked,
+        failed: verify(&mut output, cache, &global.retry),
+    };
+
+    let mut text = String::new();
+    for failed in &report.failed {
+        writeln!(text, "failed: {}", failed)?;
     }
+    writeln!(
+        text,
+        "{} of {} drivers failed verification",
+        report.failed.len(),
+        report.checked
+    )?;
+    global.report(&report, text)?;
 
-    Ok(())
+    Ok(problems_if(!report.failed.is_empty()))
 }
 
-fn fetch_manifest_from_network() -> Result<String> {
-    Ok(ureq::get(MANIFEST_URL)
-        .set("User-Agent", USER_AGENT)
-        .call()?
-        .into_string()?)
+/// Compare the two snapshots, printing the changes and writing them to `changes.json`.
+fn diff(global: &Global, options: &DiffOptions) -> Result<Status> {
+    let old = load_snapshot(&options.old)?;
+    let new = load_snapshot(&options.new)?;
+
+    let changes = Changes::new(&old, &new);
+    global.report(&changes, &changes)?;
+    write_json(&options.json, &changes)?;
+    Ok(Status::Done)
 }
 
-fn clean_dist_directory(dist: &Path, versions: &Path) -> Result<()> {
-    if dist.exists() {
-        remove_dir_all(dist)?;
+/// The request of `query` and `install`, detecting the installed Microsoft Edge if there is none.
+fn request(global: &Global, options: &QueryOptions) -> Result<Request> {
+    match &options.request {
+        Some(request) => Ok(*request),
+        None => {
+            let browser = installed_version(&options.edge_command)?;
+            global.debug(format_args!("found Microsoft Edge {}", browser));
+            Ok(Request::Browser(browser))
+        }
     }
-    create_dir(dist)?;
-    create_dir(versions)?;
-    Ok(())
 }
 
-fn parse_version_and_platform(s: &str) -> Option<(Version, Platform)> {
-    let mut sides = s.split('/');
-    let version = Version(sides.next()?.to_string());
-    let platform_raw = sides.next()?;
+/// A client of the published cache: `--source` if it's set, `--out` otherwise.
+fn client(global: &Global) -> Client {
+    let location = match &global.source {
+        Some(source) => Location::new(source),
+        None => Location::Dir(global.out.clone()),
+    };
+    Client::new(location, global.retry.clone())
+}
 
-    if sides.next().is_some() {
-        eprintln!("unknown version/platform format: {}", s);
-        return None;
+/// The driver cache in `dir`, or the per-user one.
+fn driver_cache(dir: Option<&PathBuf>) -> Result<DriverCache> {
+    match dir {
+        Some(dir) => Ok(DriverCache::new(dir)),
+        None => DriverCache::user(|name| env::var(name).ok()),
     }
+}
 
-    let platform = Platform(
-        platform_raw
-            .strip_prefix("edgedriver_")?
-            .strip_suffix(".zip")?
-            .to_string(),
-    );
+/// Resolve a driver against the published cache.
+fn query(global: &Global, options: QueryOptions) -> Result<Status> {
+    let request = request(global, &options)?;
+    let driver = client(global).resolve(&request, &options.platform)?;
+    global.report(
+        &driver,
+        format_args!(
+            "{} {} {}\n",
+            driver.version, options.platform, driver.properties.url
+        ),
+    )?;
+    Ok(Status::Done)
+}
+
+/// The output of `install`.
+#[derive(Debug, Serialize)]
+struct Installed {
+    version: Version,
+    platform: Platform,
+    /// The installed `msedgedriver` executable.
+    path: PathBuf,
+}
+
+/// Resolve a driver against the published cache and install it into the per-user cache, unless
+/// it's already there.
+fn install(global: &Global, options: InstallOptions) -> Result<Status> {
+    let request = request(global, &options.query)?;
+    let platform = options.query.platform;
+    let cache = driver_cache(options.dir.as_ref())?;
 
-    Some((version, platform))
+    let client = client(global);
+    let driver = client.resolve(&request, &platform)?;
+    global.debug(format_args!(
+        "installing {} {} into {}",
+        driver.version,
+        platform,
+        cache.root().display()
+    ));
+    let installed = Installed {
+        version: driver.version,
+        path: cache.install(&client, &driver, &platform)?,
+        platform,
+    };
+
+    global.report(
+        &installed,
+        format_args!(
+            "{} {} {}\n",
+            installed.version,
+            installed.platform,
+            installed.path.display()
+        ),
+    )?;
+    Ok(Status::Done)
 }
 
-#[test]
-fn version_and_platform() {
-    let (version, platform) =
-        parse_version_and_platform("100.0.1154.0/edgedriver_arm64.zip").unwrap();
+/// List the drivers in the per-user cache, or prune it.
+fn cache(global: &Global, options: &CacheOptions) -> Result<Status> {
+    let cache = driver_cache(options.dir.as_ref())?;
+
+    let mut warnings = Vec::new();
+    let (drivers, verb) = match &options.prune {
+        Some(prune) => (cache.prune(prune, &mut warnings)?, "removed"),
+        None => (cache.list(&mut warnings)?, "cached"),
+    };
+    warnings.iter().for_each(warn);
 
-    assert_eq!(version.0, "100.0.1154.0");
-    assert_eq!(platform.0, "arm64");
+    let mut text = String::new();
+    for driver in &drivers {
+        writeln!(
+            text,
+            "{} {} {} {} bytes, last used {}",
+            driver.version, driver.platform, driver.etag, driver.size, driver.last_used
+        )?;
+    }
+    writeln!(
+        text,
+        "{} {} drivers, {} bytes in {}",
+        verb,
+        drivers.len(),
+        drivers.iter().map(|driver| driver.size).sum::<u64>(),
+        cache.root().display()
+    )?;
+    global.report(&drivers, text)?;
+    Ok(Status::Done)
 }
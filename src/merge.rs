@@ -0,0 +1,97 @@
+//! Merging a fresh listing into the previously published output, for incremental builds.
+
+use std::{
+    ffi::OsStr,
+    fs::{read_dir, read_to_string},
+    path::Path,
+};
+
+use anyhow::{Context, Result};
+
+use crate::{timestamp::Timestamp, Output, Version};
+
+/// Load every `<version>.json` file of a previously published `versions` directory.
+///
+/// A missing directory (e.g. on the very first run) is treated as an empty output.
+pub fn load_versions(versions: &Path) -> Result<Output> {
+    let mut output = Output::default();
+    if !versions.exists() {
+        return Ok(output);
+    }
+
+    for entry in read_dir(versions)? {
+        let path = entry?.path();
+        if path.extension() != Some(OsStr::new("json")) {
+            continue;
+        }
+
+        let version: Version = match path.file_stem() {
+            Some(stem) => stem
+                .to_string_lossy()
+                .parse()
+                .with_context(|| format!("unexpected version file {}", path.display()))?,
+            None => continue,
+        };
+
+        let content = read_to_string(&path)?;
+        let platforms = serde_json::from_str(&content)
+            .with_context(|| format!("failed to parse {}", path.display()))?;
+        output.0.insert(version, platforms);
+    }
+
+    Ok(output)
+}
+
+/// Merge a freshly listed `current` output on top of the `previous` one.
+///
+/// Entries that are no longer listed upstream are kept and marked with `removed_at`, while entries
+/// that are listed (again) always take the current upstream properties.
+pub fn merge(previous: Output, mut current: Output, removed_at: Timestamp) -> Output {
+    for (version, platforms) in previous.0 {
+        let listed = current.0.entry(version).or_default();
+        for (platform, mut properties) in platforms {
+            listed.entry(platform).or_insert_with(|| {
+                properties.removed_upstream_at.get_or_insert(removed_at);
+                properties
+            });
+        }
+    }
+
+    current
+}
+
+#[test]
+fn removed_entries_are_kept_and_marked() {
+    use crate::{Platform, Properties};
+    use std::collections::BTreeMap;
+
+    let entry = |version: &str, platform: &str, removed: Option<&str>| {
+        let properties = Properties {
+            url: format!("{}/{}", version, platform),
+            removed_upstream_at: removed.map(|at| at.parse().unwrap()),
+            ..Default::default()
+        };
+        (
+            version.parse::<Version>().unwrap(),
+            BTreeMap::from([(Platform::from_name(platform), properties)]),
+        )
+    };
+
+    let previous = Output(BTreeMap::from([
+        entry("1.0.0.0", "win64", None),
+        entry("2.0.0.0", "win64", Some("2022-01-01T00:00:00Z")),
+        entry("3.0.0.0", "win64", Some("2022-01-01T00:00:00Z")),
+    ]));
+    let current = Output(BTreeMap::from([entry("3.0.0.0", "win64", None)]));
+
+    let merged = merge(previous, current, "2022-06-01T00:00:00Z".parse().unwrap());
+    let removed_at = |version: &str| {
+        merged.0[&version.parse::<Version>().unwrap()][&Platform::Win64]
+            .removed_upstream_at
+            .map(|at| at.to_string())
+    };
+
+    assert_eq!(removed_at("1.0.0.0"), Some("2022-06-01T00:00:00Z".into()));
+    assert_eq!(removed_at("2.0.0.0"), Some("2022-01-01T00:00:00Z".into()));
+    assert_eq!(removed_at("3.0.0.0"), None);
+}
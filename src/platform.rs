@@ -0,0 +1,244 @@
+//! The platforms drivers are published for, and the OS and architecture each one runs on.
+
+use std::{
+    cmp::Ordering,
+    convert::Infallible,
+    env, fmt,
+    hash::{Hash, Hasher},
+    str::FromStr,
+};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// The target an msedgedriver archive was built for, i.e. the `*` in `edgedriver_*.zip`.
+///
+/// Platforms compare, hash and order by [`Platform::name`], so an `Unknown` holding a known name
+/// is the same platform as the known variant.
+#[derive(Debug, Clone)]
+pub enum Platform {
+    /// 32-bit Windows.
+    Win32,
+    /// 64-bit Windows.
+    Win64,
+    /// Windows on ARM.
+    Arm64,
+    /// macOS on Intel.
+    Mac64,
+    /// macOS on Apple silicon.
+    Mac64M1,
+    /// 64-bit Linux.
+    Linux64,
+    /// A platform this cache doesn't know about yet, kept as-is so it still ends up in the output.
+    Unknown(String),
+}
+
+/// The operating system a [`Platform`] targets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Os {
+    /// Windows.
+    Windows,
+    /// macOS.
+    #[serde(rename = "macos")]
+    Mac,
+    /// Linux.
+    Linux,
+}
+
+/// The CPU architecture a [`Platform`] targets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Arch {
+    /// 32-bit x86.
+    X86,
+    /// 64-bit x86.
+    X64,
+    /// 64-bit ARM.
+    Arm64,
+}
+
+/// The metadata of a platform, as published in `platforms.json`.
+#[derive(Debug, Serialize)]
+pub struct PlatformInfo {
+    /// The operating system, unless the platform is unknown.
+    pub os: Option<Os>,
+    /// The CPU architecture, unless the platform is unknown.
+    pub arch: Option<Arch>,
+    /// Whether the platform is one of [`Platform::KNOWN`].
+    pub known: bool,
+}
+
+impl Platform {
+    /// Every platform known to this cache.
+    pub const KNOWN: [Platform; 6] = [
+        Self::Win32,
+        Self::Win64,
+        Self::Arm64,
+        Self::Mac64,
+        Self::Mac64M1,
+        Self::Linux64,
+    ];
+
+    /// Look up a platform by its upstream name, falling back to [`Platform::Unknown`].
+    pub fn from_name(name: &str) -> Self {
+        Self::KNOWN
+            .into_iter()
+            .find(|known| known.name() == name)
+            .unwrap_or_else(|| Self::Unknown(name.into()))
+    }
+
+    /// The platform this program was built for, if drivers are published for it.
+    pub fn host() -> Option<Self> {
+        match (env::consts::OS, env::consts::ARCH) {
+            ("windows", "x86") => Some(Self::Win32),
+            ("windows", "x86_64") => Some(Self::Win64),
+            ("windows", "aarch64") => Some(Self::Arm64),
+            ("macos", "x86_64") => Some(Self::Mac64),
+            ("macos", "aarch64") => Some(Self::Mac64M1),
+            ("linux", "x86_64") => Some(Self::Linux64),
+            _ => None,
+        }
+    }
+
+    /// The name used upstream, e.g. `mac64_m1`.
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Win32 => "win32",
+            Self::Win64 => "win64",
+            Self::Arm64 => "arm64",
+            Self::Mac64 => "mac64",
+            Self::Mac64M1 => "mac64_m1",
+            Self::Linux64 => "linux64",
+            Self::Unknown(name) => name,
+        }
+    }
+
+    /// The name Chrome for Testing uses for the equivalent platform, e.g. `mac-arm64`.
+    pub fn cft_name(&self) -> &str {
+        match self {
+            Self::Win32 => "win32",
+            Self::Win64 => "win64",
+            Self::Arm64 => "win-arm64",
+            Self::Mac64 => "mac-x64",
+            Self::Mac64M1 => "mac-arm64",
+            Self::Linux64 => "linux64",
+            Self::Unknown(name) => name,
+        }
+    }
+
+    /// The operating system this platform targets, `None` for unknown platforms.
+    pub fn os(&self) -> Option<Os> {
+        match self {
+            Self::Win32 | Self::Win64 | Self::Arm64 => Some(Os::Windows),
+            Self::Mac64 | Self::Mac64M1 => Some(Os::Mac),
+            Self::Linux64 => Some(Os::Linux),
+            Self::Unknown(_) => None,
+        }
+    }
+
+    /// The CPU architecture this platform targets, `None` for unknown platforms.
+    pub fn arch(&self) -> Option<Arch> {
+        match self {
+            Self::Win32 => Some(Arch::X86),
+            Self::Win64 | Self::Mac64 | Self::Linux64 => Some(Arch::X64),
+            Self::Arm64 | Self::Mac64M1 => Some(Arch::Arm64),
+            Self::Unknown(_) => None,
+        }
+    }
+
+    /// Whether this is one of [`Platform::KNOWN`] rather than [`Platform::Unknown`].
+    pub fn is_known(&self) -> bool {
+        !matches!(self, Self::Unknown(_))
+    }
+
+    /// The metadata of this platform.
+    pub fn info(&self) -> PlatformInfo {
+        PlatformInfo {
+            os: self.os(),
+            arch: self.arch(),
+            known: self.is_known(),
+        }
+    }
+}
+
+impl FromStr for Platform {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self::from_name(s))
+    }
+}
+
+impl PartialEq for Platform {
+    fn eq(&self, other: &Self) -> bool {
+        self.name() == other.name()
+    }
+}
+
+impl Eq for Platform {}
+
+impl Hash for Platform {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.name().hash(state)
+    }
+}
+
+/// Platforms are ordered by name, so they serialize in the same order whether or not they're known.
+impl Ord for Platform {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.name().cmp(other.name())
+    }
+}
+
+impl PartialOrd for Platform {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl Serialize for Platform {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+impl<'de> Deserialize<'de> for Platform {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Ok(Self::from_name(&s))
+    }
+}
+
+#[test]
+fn known_and_unknown_platforms() {
+    let parse = Platform::from_name;
+
+    assert_eq!(parse("mac64_m1"), Platform::Mac64M1);
+    assert_eq!(parse("mac64_m1").os(), Some(Os::Mac));
+    assert_eq!(parse("mac64_m1").arch(), Some(Arch::Arm64));
+    assert_eq!(parse("win32").arch(), Some(Arch::X86));
+
+    let new = parse("linux_arm64");
+    assert_eq!(new, Platform::Unknown("linux_arm64".into()));
+    assert!(!new.is_known());
+    assert_eq!(new.os(), None);
+    assert_eq!(new.to_string(), "linux_arm64");
+    assert_eq!("win64".parse(), Ok(Platform::Win64));
+}
+
+#[test]
+fn unknown_platforms_with_known_names() {
+    use std::collections::{BTreeSet, HashSet};
+
+    let unknown = Platform::Unknown("win64".into());
+    assert_eq!(unknown, Platform::Win64);
+    assert_eq!(unknown.cmp(&Platform::Win64), Ordering::Equal);
+    assert_eq!(HashSet::from([unknown.clone(), Platform::Win64]).len(), 1);
+    assert_eq!(BTreeSet::from([unknown, Platform::Win64]).len(), 1);
+}
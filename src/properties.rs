@@ -0,0 +1,444 @@
+//! The properties of a driver archive, parsed from the listing and published in the version files.
+
+use std::{fmt, str::FromStr};
+
+use anyhow::{anyhow, ensure, Error, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{de, ser::SerializeStruct, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{timestamp::Timestamp, verify::Verification, Blob};
+
+/// The properties of a single driver archive, as published in the version files.
+///
+/// Values that are missing or malformed upstream are `null`, unless the properties keep the
+/// [`raw`](Properties::raw) upstream strings, which are then published in their place.
+#[derive(Debug, Default, Deserialize)]
+#[serde(try_from = "StoredProperties")]
+pub struct Properties {
+    /// Where the archive is downloaded from.
+    pub url: String,
+    /// The `Last-Modified` of the blob.
+    pub last_modified: Option<Timestamp>,
+    /// The `ETag` of the blob, as listed (without quotes).
+    pub etag: String,
+    /// The `Content-MD5` of the blob, base64 encoded like upstream.
+    pub md5: Option<Base64>,
+    /// The same digest as `md5`, hex encoded like most checksum tools print it.
+    pub md5_hex: Option<Hex>,
+    /// The size of the archive, in bytes.
+    pub content_length: Option<u64>,
+    /// The `Content-Type` of the blob, empty if it wasn't listed.
+    pub content_type: String,
+    /// Only for drivers that passed `--verify`.
+    pub sha256: Option<Hex>,
+    /// Only for drivers that passed `--verify`.
+    pub sha512: Option<Hex>,
+    /// Only with `--verify`.
+    pub verification: Option<Verification>,
+    /// When an incremental build first found the blob missing from the listing. Only for drivers
+    /// that were removed upstream.
+    pub removed_upstream_at: Option<Timestamp>,
+    /// The unparsed values, published in place of the typed ones for consumers of the old
+    /// all-strings format. Only with `--raw-properties`.
+    pub raw: Option<RawProperties>,
+}
+
+/// The properties that are parsed into typed values, exactly as they were listed upstream.
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RawProperties {
+    /// The listed `Last-Modified`.
+    #[serde(rename = "lastModified")]
+    pub last_modified: String,
+    /// The listed `Content-MD5`.
+    pub md5: String,
+    /// The listed `Content-Length`.
+    #[serde(rename = "contentLength")]
+    pub content_length: String,
+}
+
+impl Properties {
+    /// Parse the properties of a listed blob.
+    ///
+    /// Values that don't parse are left out and described in `malformed`, while values that are
+    /// missing altogether are silently left out. With `raw`, the original strings are kept too.
+    pub fn from_blob(blob: Blob, raw: bool, malformed: &mut Vec<String>) -> Self {
+        let listed = blob.properties;
+        let name = &blob.name;
+        let last_modified = parse_listed(
+            name,
+            "Last-Modified",
+            &listed.last_modified,
+            Timestamp::parse_http_date,
+            malformed,
+        );
+        let content_length = parse_listed(
+            name,
+            "Content-Length",
+            &listed.content_length,
+            |s| s.parse().ok(),
+            malformed,
+        );
+        let md5: Option<Base64> = parse_listed(
+            name,
+            "Content-MD5",
+            &listed.content_md5,
+            |s| s.parse().ok(),
+            malformed,
+        );
+
+        Self {
+            url: blob.url,
+            last_modified,
+            etag: listed.etag,
+            md5_hex: md5.as_ref().map(|Base64(bytes)| Hex(bytes.clone())),
+            md5,
+            content_length,
+            content_type: listed.content_type,
+            sha256: None,
+            sha512: None,
+            verification: None,
+            removed_upstream_at: None,
+            raw: raw.then_some(RawProperties {
+                last_modified: listed.last_modified,
+                md5: listed.content_md5,
+                content_length: listed.content_length,
+            }),
+        }
+    }
+
+    /// The typed values written back in the form they're listed in upstream, for drivers that
+    /// don't have their [`raw`](Properties::raw) strings.
+    pub fn listed(&self) -> RawProperties {
+        RawProperties {
+            last_modified: self
+                .last_modified
+                .map(Timestamp::to_http_date)
+                .unwrap_or_default(),
+            md5: self.md5.as_ref().map(Base64::to_string).unwrap_or_default(),
+            content_length: self
+                .content_length
+                .map(|length| length.to_string())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// The bytes of the listed MD5 digest, from `md5Hex` or, in caches published without it,
+    /// from `md5`.
+    pub fn md5_digest(&self) -> Option<&[u8]> {
+        match (&self.md5_hex, &self.md5) {
+            (Some(Hex(bytes)), _) | (None, Some(Base64(bytes))) => Some(bytes),
+            (None, None) => None,
+        }
+    }
+}
+
+/// Parse a listed property, recording it in `malformed` when it's there but doesn't parse.
+fn parse_listed<T>(
+    name: &str,
+    header: &str,
+    value: &str,
+    parse: impl FnOnce(&str) -> Option<T>,
+    malformed: &mut Vec<String>,
+) -> Option<T> {
+    if value.is_empty() {
+        return None;
+    }
+
+    let parsed = parse(value);
+    if parsed.is_none() {
+        malformed.push(format!("{}: malformed {} {:?}", name, header, value));
+    }
+    parsed
+}
+
+/// The bytes of an MD5 digest, written as base64.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Base64(pub Vec<u8>);
+
+/// The bytes of a digest, written as lowercase hex.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hex(pub Vec<u8>);
+
+impl FromStr for Base64 {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = STANDARD.decode(s)?;
+        ensure!(
+            bytes.len() == 16,
+            "expected a 16 byte MD5 digest, found {} bytes",
+            bytes.len()
+        );
+        Ok(Self(bytes))
+    }
+}
+
+impl FromStr for Hex {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self(hex::decode(s)?))
+    }
+}
+
+impl fmt::Display for Base64 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&STANDARD.encode(&self.0))
+    }
+}
+
+impl fmt::Display for Hex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&hex::encode(&self.0))
+    }
+}
+
+impl Serialize for Base64 {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl Serialize for Hex {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Hex {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(de::Error::custom)
+    }
+}
+
+/// With [`raw`](Properties::raw) strings, those are published in place of the typed values.
+impl Serialize for Properties {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("Properties", 11)?;
+        state.serialize_field("url", &self.url)?;
+        match &self.raw {
+            Some(raw) => state.serialize_field("lastModified", &raw.last_modified)?,
+            None => state.serialize_field("lastModified", &self.last_modified)?,
+        }
+        state.serialize_field("etag", &self.etag)?;
+        match &self.raw {
+            Some(raw) => state.serialize_field("md5", &raw.md5)?,
+            None => state.serialize_field("md5", &self.md5)?,
+        }
+        state.serialize_field("md5Hex", &self.md5_hex)?;
+        match &self.raw {
+            Some(raw) => state.serialize_field("contentLength", &raw.content_length)?,
+            None => state.serialize_field("contentLength", &self.content_length)?,
+        }
+        state.serialize_field("contentType", &self.content_type)?;
+        serialize_some(&mut state, "sha256", &self.sha256)?;
+        serialize_some(&mut state, "sha512", &self.sha512)?;
+        serialize_some(&mut state, "verification", &self.verification)?;
+        serialize_some(&mut state, "removedUpstreamAt", &self.removed_upstream_at)?;
+        state.end()
+    }
+}
+
+/// Serialize a field that is left out when it's `None`.
+fn serialize_some<S: SerializeStruct>(
+    state: &mut S,
+    key: &'static str,
+    value: &Option<impl Serialize>,
+) -> Result<(), S::Error> {
+    match value {
+        Some(value) => state.serialize_field(key, value),
+        None => state.skip_field(key),
+    }
+}
+
+/// [`Properties`] as written by any version of this tool: with typed values, with the raw upstream
+/// strings in their place (`--raw-properties`, or before values were typed), or with the raw
+/// strings nested under `raw`.
+#[derive(Deserialize)]
+struct StoredProperties {
+    url: String,
+    #[serde(rename = "lastModified", default)]
+    last_modified: Option<Stored>,
+    etag: String,
+    #[serde(default)]
+    md5: Option<Stored>,
+    #[serde(rename = "md5Hex", default)]
+    md5_hex: Option<Hex>,
+    #[serde(rename = "contentLength", default)]
+    content_length: Option<Stored>,
+    #[serde(rename = "contentType", default)]
+    content_type: String,
+    #[serde(default)]
+    sha256: Option<Hex>,
+    #[serde(default)]
+    sha512: Option<Hex>,
+    #[serde(default)]
+    verification: Option<Verification>,
+    #[serde(rename = "removedUpstreamAt", default)]
+    removed_upstream_at: Option<Timestamp>,
+    #[serde(default)]
+    raw: Option<RawProperties>,
+}
+
+/// A stored property value, typed or as the raw upstream string.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Stored {
+    Number(u64),
+    String(String),
+}
+
+impl fmt::Display for Stored {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Number(n) => n.fmt(f),
+            Self::String(s) => f.write_str(s),
+        }
+    }
+}
+
+impl TryFrom<StoredProperties> for Properties {
+    type Error = Error;
+
+    fn try_from(stored: StoredProperties) -> Result<Self, Self::Error> {
+        let string =
+            |value: &Option<Stored>| value.as_ref().map(Stored::to_string).unwrap_or_default();
+        let last_modified = string(&stored.last_modified);
+        let md5 = string(&stored.md5);
+        let content_length = string(&stored.content_length);
+
+        // only the all-strings format has a string length, and its strings are kept as raw ones
+        let raw = match (stored.raw, &stored.content_length) {
+            (Some(raw), _) => Some(raw),
+            (None, Some(Stored::String(_))) => Some(RawProperties {
+                last_modified: last_modified.clone(),
+                md5: md5.clone(),
+                content_length: content_length.clone(),
+            }),
+            (None, _) => None,
+        };
+        let lenient = raw.is_some();
+
+        Ok(Self {
+            url: stored.url,
+            last_modified: parse_stored("lastModified", &last_modified, lenient)?,
+            etag: stored.etag,
+            md5: parse_stored("md5", &md5, lenient)?,
+            md5_hex: stored.md5_hex,
+            content_length: parse_stored("contentLength", &content_length, lenient)?,
+            content_type: stored.content_type,
+            sha256: stored.sha256,
+            sha512: stored.sha512,
+            verification: stored.verification,
+            removed_upstream_at: stored.removed_upstream_at,
+            raw,
+        })
+    }
+}
+
+/// Parse a stored property, empty when it was missing upstream. With `lenient`, a value that
+/// doesn't parse is left out, as it was when it was listed.
+fn parse_stored<T>(name: &str, value: &str, lenient: bool) -> Result<Option<T>>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    match value.parse() {
+        _ if value.is_empty() => Ok(None),
+        Ok(parsed) => Ok(Some(parsed)),
+        Err(_) if lenient => Ok(None),
+        Err(error) => Err(anyhow!("invalid {} {:?}: {}", name, value, error)),
+    }
+}
+
+#[test]
+fn blob_properties_are_parsed() {
+    use crate::BlobProperties;
+
+    let blob = |properties| Blob {
+        name: "100.0.1154.0/edgedriver_win64.zip".into(),
+        url: "https://example.com/win64.zip".into(),
+        properties,
+    };
+    let mut malformed = Vec::new();
+
+    let properties = Properties::from_blob(
+        blob(BlobProperties {
+            last_modified: "Tue, 15 Mar 2022 00:26:40 GMT".into(),
+            etag: "0x8DA061B6A2F2E0A".into(),
+            content_length: "8968917".into(),
+            content_type: "application/octet-stream".into(),
+            content_md5: "1B2M2Y8AsgTpgAmY7PhCfg==".into(),
+        }),
+        false,
+        &mut malformed,
+    );
+    assert!(malformed.is_empty());
+    assert_eq!(
+        serde_json::to_value(&properties).unwrap(),
+        serde_json::json!({
+            "url": "https://example.com/win64.zip",
+            "lastModified": "2022-03-15T00:26:40Z",
+            "etag": "0x8DA061B6A2F2E0A",
+            "md5": "1B2M2Y8AsgTpgAmY7PhCfg==",
+            "md5Hex": "d41d8cd98f00b204e9800998ecf8427e",
+            "contentLength": 8968917,
+            "contentType": "application/octet-stream",
+        })
+    );
+
+    let properties = Properties::from_blob(
+        blob(BlobProperties {
+            last_modified: "yesterday".into(),
+            content_length: "-1".into(),
+            content_md5: "bm90IGFuIG1kNQ==".into(),
+            ..Default::default()
+        }),
+        true,
+        &mut malformed,
+    );
+    assert_eq!(malformed.len(), 3);
+    assert!(malformed[0].contains("malformed Last-Modified \"yesterday\""));
+    assert_eq!(properties.last_modified, None);
+    assert_eq!(properties.content_length, None);
+    let value = serde_json::to_value(&properties).unwrap();
+    assert_eq!(value["lastModified"], "yesterday");
+    assert_eq!(value["md5"], "bm90IGFuIG1kNQ==");
+    assert_eq!(value["md5Hex"], serde_json::Value::Null);
+    assert_eq!(value["contentLength"], "-1");
+    assert_eq!(value.get("raw"), None);
+
+    // the raw strings survive being read back, e.g. by an incremental build
+    let read: Properties = serde_json::from_value(value).unwrap();
+    assert_eq!(read.raw, properties.raw);
+    assert_eq!(read.content_length, None);
+}
+
+#[test]
+fn old_version_files_still_parse() {
+    let properties: Properties = serde_json::from_value(serde_json::json!({
+        "url": "https://example.com/win64.zip",
+        "lastModified": "Tue, 15 Mar 2022 00:26:40 GMT",
+        "etag": "0x8DA061B6A2F2E0A",
+        "md5": "",
+        "contentLength": "8968917",
+    }))
+    .unwrap();
+
+    assert_eq!(
+        properties.last_modified.unwrap().to_string(),
+        "2022-03-15T00:26:40Z"
+    );
+    assert_eq!(properties.md5, None);
+    assert_eq!(properties.content_length, Some(8968917));
+    assert_eq!(properties.listed(), properties.raw.unwrap());
+
+    let invalid = serde_json::json!({
+        "url": "https://example.com/win64.zip",
+        "lastModified": "yesterday",
+        "etag": "0x8DA061B6A2F2E0A",
+        "contentLength": 8968917,
+    });
+    assert!(serde_json::from_value::<Properties>(invalid).is_err());
+}
@@ -0,0 +1,146 @@
+//! Retrying failed requests against upstream, with exponential backoff and jitter.
+
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    thread::sleep,
+    time::Duration,
+};
+
+use anyhow::Result;
+use ureq::ErrorKind;
+
+/// How failed requests against the upstream endpoint are retried.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first one.
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled after every following attempt.
+    pub base_delay: Duration,
+    /// Upper bound for the delay between two attempts.
+    pub max_delay: Duration,
+    /// Fraction (`0.0..=1.0`) of each delay that is randomized.
+    pub jitter: f64,
+    /// HTTP status codes that are worth retrying.
+    pub retryable_statuses: Vec<u16>,
+    /// Transport (connection, DNS, IO) errors that are worth retrying.
+    pub retryable_transport: Vec<ErrorKind>,
+    /// Called with a description of every failed attempt that is retried, e.g. to log it. Does
+    /// nothing by default.
+    pub on_retry: fn(&str),
+}
+
+/// The error of a single attempt in [`RetryPolicy::run`].
+///
+/// ureq's error type is large, so it's boxed to keep the (common) success path small. Anything that
+/// converts into a `ureq::Error`, like an `io::Error`, converts into this with `?`.
+#[derive(Debug)]
+pub struct RequestError(Box<ureq::Error>);
+
+impl<E: Into<ureq::Error>> From<E> for RequestError {
+    fn from(error: E) -> Self {
+        Self(Box::new(error.into()))
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            jitter: 0.5,
+            retryable_statuses: vec![408, 425, 429, 500, 502, 503, 504],
+            retryable_transport: vec![
+                ErrorKind::Dns,
+                ErrorKind::ConnectionFailed,
+                ErrorKind::Io,
+                ErrorKind::ProxyConnect,
+            ],
+            on_retry: |_| {},
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Run `f` until it succeeds, fails with a non-retryable error, or runs out of attempts.
+    ///
+    /// `what` is only used to describe the request to [`on_retry`](Self::on_retry).
+    pub fn run<T>(&self, what: &str, mut f: impl FnMut() -> Result<T, RequestError>) -> Result<T> {
+        let mut attempt = 1;
+        loop {
+            let RequestError(error) = match f() {
+                Ok(value) => return Ok(value),
+                Err(error) => error,
+            };
+
+            if attempt >= self.max_attempts || !self.is_retryable(&error) {
+                return Err((*error).into());
+            }
+
+            let delay = self.delay(attempt);
+            (self.on_retry)(&format!(
+                "attempt {}/{} to fetch {} failed: {}; retrying in {:?}",
+                attempt, self.max_attempts, what, error, delay
+            ));
+            sleep(delay);
+            attempt += 1;
+        }
+    }
+
+    fn is_retryable(&self, error: &ureq::Error) -> bool {
+        match error {
+            ureq::Error::Status(status, _) => self.retryable_statuses.contains(status),
+            ureq::Error::Transport(transport) => {
+                self.retryable_transport.contains(&transport.kind())
+            }
+        }
+    }
+
+    /// The delay after the given (1-based) failed attempt.
+    fn delay(&self, attempt: u32) -> Duration {
+        let exponential = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt - 1))
+            .min(self.max_delay);
+
+        let jitter = self.jitter.clamp(0.0, 1.0) * random_fraction();
+        exponential.mul_f64(1.0 - jitter)
+    }
+}
+
+/// A random number in `0.0..1.0`, good enough to spread out retries without pulling in `rand`.
+fn random_fraction() -> f64 {
+    let bits = RandomState::new().build_hasher().finish();
+    (bits >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[test]
+fn delay_grows_exponentially_up_to_the_cap() {
+    let policy = RetryPolicy {
+        base_delay: Duration::from_millis(100),
+        max_delay: Duration::from_millis(500),
+        jitter: 0.0,
+        ..Default::default()
+    };
+
+    assert_eq!(policy.delay(1), Duration::from_millis(100));
+    assert_eq!(policy.delay(2), Duration::from_millis(200));
+    assert_eq!(policy.delay(3), Duration::from_millis(400));
+    assert_eq!(policy.delay(4), Duration::from_millis(500));
+    assert_eq!(policy.delay(40), Duration::from_millis(500));
+}
+
+#[test]
+fn jitter_only_shortens_the_delay() {
+    let policy = RetryPolicy {
+        base_delay: Duration::from_millis(100),
+        jitter: 0.5,
+        ..Default::default()
+    };
+
+    for _ in 0..100 {
+        let delay = policy.delay(1);
+        assert!(delay >= Duration::from_millis(50) && delay <= Duration::from_millis(100));
+    }
+}
@@ -0,0 +1,111 @@
+//! Serving a published `dist` over HTTP, to try the cache (or the client) locally.
+
+use std::{
+    fs::read,
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    path::{Component, Path},
+};
+
+use anyhow::{Error, Result};
+
+/// Answer `GET` and `HEAD` requests for the files under `root`, one connection at a time, until
+/// `listener` fails. Requests that can't be answered are passed to `on_error`, and serving goes on.
+pub fn serve(listener: &TcpListener, root: &Path, on_error: impl Fn(Error)) -> Result<()> {
+    for stream in listener.incoming() {
+        if let Err(e) = respond(stream?, root) {
+            on_error(e);
+        }
+    }
+
+    Ok(())
+}
+
+fn respond(mut stream: TcpStream, root: &Path) -> Result<()> {
+    let mut reader = BufReader::new(&stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    let target = parts.next().unwrap_or_default();
+    let path = target.split(['?', '#']).next().unwrap_or_default();
+
+    let (status, content_type, body) = match method {
+        "GET" | "HEAD" => match file(root, path) {
+            Some(body) => ("200 OK", content_type(path), body),
+            None => ("404 Not Found", "text/plain", b"not found\n".to_vec()),
+        },
+        _ => (
+            "405 Method Not Allowed",
+            "text/plain",
+            b"method not allowed\n".to_vec(),
+        ),
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    )?;
+    if method != "HEAD" {
+        stream.write_all(&body)?;
+    }
+    Ok(())
+}
+
+/// The content of the file at the URL path `path`, if it's a file under `root`.
+fn file(root: &Path, path: &str) -> Option<Vec<u8>> {
+    let relative = Path::new(path.trim_start_matches('/'));
+    // never serve anything outside of root
+    if !relative
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)))
+    {
+        return None;
+    }
+
+    let path = root.join(relative);
+    path.is_file().then(|| read(path).ok())?
+}
+
+fn content_type(path: &str) -> &'static str {
+    match path.rsplit_once('.').map(|(_, extension)| extension) {
+        Some("json") => "application/json",
+        Some("xml") => "application/xml",
+        Some("zip") => "application/zip",
+        _ => "application/octet-stream",
+    }
+}
+
+#[test]
+fn files_under_the_root_are_served() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root = tmp.path().join("dist");
+    std::fs::create_dir(&root).unwrap();
+    std::fs::write(root.join("index.json"), "{}").unwrap();
+    std::fs::write(tmp.path().join("secret"), "").unwrap();
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    std::thread::spawn(move || serve(&listener, &root, |_| {}));
+
+    let response = ureq::get(&format!("{}/index.json", base)).call().unwrap();
+    assert_eq!(response.content_type(), "application/json");
+    assert_eq!(response.into_string().unwrap(), "{}");
+
+    for path in ["/missing.json", "/../secret", "/"] {
+        match ureq::get(&format!("{}{}", base, path)).call() {
+            Err(ureq::Error::Status(404, _)) => {}
+            other => panic!("expected a 404 for {}, got {:?}", path, other.map(|_| ())),
+        }
+    }
+}
@@ -0,0 +1,482 @@
+//! Loading the container listing from the network, a saved file or stdin.
+
+use std::{
+    fs::{read_to_string, File},
+    io::{stdin, Read},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use quick_xml::de::from_str;
+use serde::{Deserialize, Serialize};
+
+use crate::{retry::RetryPolicy, EnumerationResults, USER_AGENT};
+
+/// Where the container listing is read from.
+#[derive(Debug)]
+pub enum Source {
+    /// Fetch (and page through) the listing from the first of these URLs that works.
+    Network(Vec<String>),
+    /// Read a previously saved listing from a file.
+    File(PathBuf),
+    /// Read a previously saved listing from stdin.
+    Stdin,
+}
+
+/// The container listing, as raw XML pages and parsed into blobs.
+#[derive(Debug)]
+pub struct Listing {
+    /// The raw XML of each page, in the order they were fetched.
+    pub pages: Vec<String>,
+    /// The blobs of all pages merged together.
+    pub results: EnumerationResults,
+    /// Where the listing was actually read from.
+    pub origin: Origin,
+    /// Problems with the listing that didn't stop it from being read, like a saved listing that
+    /// is missing pages.
+    pub warnings: Vec<String>,
+}
+
+/// Where a listing was actually read from, recorded in the output as `source.json`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum Origin {
+    /// Fetched from the network.
+    Network {
+        /// The URL the listing was fetched from.
+        url: String,
+        /// The validators of the fetched listing.
+        #[serde(flatten)]
+        validators: Validators,
+    },
+    /// Read from a saved listing.
+    File {
+        /// The path of the saved listing.
+        path: PathBuf,
+    },
+    /// Read from stdin.
+    Stdin,
+}
+
+/// The `ETag` and `Last-Modified` of the (first page of the) listing, to only fetch it again once
+/// it changed.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Validators {
+    /// The `ETag` header, sent back as `If-None-Match`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+    /// The `Last-Modified` header, sent back as `If-Modified-Since`.
+    #[serde(
+        rename = "lastModified",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub last_modified: Option<String>,
+}
+
+impl Origin {
+    /// Read the `source.json` of a previous run, if there is a (readable) one.
+    pub fn load(path: &Path) -> Option<Self> {
+        serde_json::from_str(&read_to_string(path).ok()?).ok()
+    }
+
+    /// The validators to fetch `url` with, if this is where it was fetched from last time.
+    fn validators_for(&self, url: &str) -> Validators {
+        match self {
+            Self::Network {
+                url: previous,
+                validators,
+            } if previous == url => validators.clone(),
+            _ => Validators::default(),
+        }
+    }
+}
+
+impl Source {
+    /// Load the listing, or `None` if it was fetched from the network and didn't change since it
+    /// was fetched into `previous`.
+    pub fn load(&self, policy: &RetryPolicy, previous: Option<&Origin>) -> Result<Option<Listing>> {
+        match self {
+            Self::Network(urls) => fetch_manifest_with_failover(urls, policy, previous),
+            Self::File(path) => {
+                let file = File::open(path)
+                    .with_context(|| format!("failed to open {}", path.display()))?;
+                read_manifest(file, Origin::File { path: path.clone() }).map(Some)
+            }
+            Self::Stdin => read_manifest(stdin().lock(), Origin::Stdin).map(Some),
+        }
+    }
+}
+
+impl Listing {
+    /// All pages spliced together into a single listing document.
+    pub fn manifest(&self) -> Result<String> {
+        concatenate_pages(&self.pages)
+    }
+}
+
+/// Try each of the URLs in order, returning the first listing that could be fetched completely.
+///
+/// Every URL that fails before the last one is reported to the policy's `on_retry`.
+fn fetch_manifest_with_failover(
+    urls: &[String],
+    policy: &RetryPolicy,
+    previous: Option<&Origin>,
+) -> Result<Option<Listing>> {
+    let mut errors = Vec::new();
+    for (i, url) in urls.iter().enumerate() {
+        let validators = previous
+            .map(|previous| previous.validators_for(url))
+            .unwrap_or_default();
+        match fetch_manifest_from_network(url, &validators, policy) {
+            Ok(listing) => return Ok(listing),
+            Err(e) => {
+                if let Some(next) = urls.get(i + 1) {
+                    (policy.on_retry)(&format!(
+                        "failed to fetch the listing from {}: {}; trying {}",
+                        url, e, next
+                    ));
+                }
+                errors.push(format!("{}: {}", url, e));
+            }
+        }
+    }
+
+    bail!("no listing source could be fetched\n{}", errors.join("\n"))
+}
+
+/// Fetch every page of the container listing, following `NextMarker` until it comes back empty.
+///
+/// The first page is requested conditionally on `validators`, and `None` is returned if it didn't
+/// change. The later pages are assumed to only change along with it.
+fn fetch_manifest_from_network(
+    url: &str,
+    validators: &Validators,
+    policy: &RetryPolicy,
+) -> Result<Option<Listing>> {
+    let mut pages = Vec::new();
+    let mut results = EnumerationResults::default();
+    let mut marker = String::new();
+
+    let (mut page, validators) = match fetch_page_from_network(url, "", validators, policy)? {
+        Some(fetched) => fetched,
+        None => return Ok(None),
+    };
+
+    loop {
+        let parsed: EnumerationResults = from_str(&page)?;
+        results.blobs.blobs.extend(parsed.blobs.blobs);
+        pages.push(page);
+
+        if parsed.next_marker.is_empty() {
+            break;
+        }
+
+        if parsed.next_marker == marker {
+            bail!("listing returned the same NextMarker twice: {}", marker);
+        }
+
+        marker = parsed.next_marker;
+        page = fetch_page_from_network(url, &marker, &Validators::default(), policy)?
+            .ok_or_else(|| anyhow!("unexpected 304 Not Modified for an unconditional request"))?
+            .0;
+    }
+
+    Ok(Some(Listing {
+        pages,
+        results,
+        origin: Origin::Network {
+            url: url.into(),
+            validators,
+        },
+        warnings: Vec::new(),
+    }))
+}
+
+/// Read a previously saved listing, e.g. a `manifest.xml` written by an earlier run.
+fn read_manifest(mut reader: impl Read, origin: Origin) -> Result<Listing> {
+    let mut manifest = String::new();
+    reader.read_to_string(&mut manifest)?;
+
+    let results: EnumerationResults = from_str(&manifest)?;
+    let mut warnings = Vec::new();
+    if !results.next_marker.is_empty() {
+        warnings.push(format!(
+            "the saved listing is a single page of a larger one (NextMarker {}), later pages are missing",
+            results.next_marker
+        ));
+    }
+
+    Ok(Listing {
+        pages: vec![manifest],
+        results,
+        origin,
+        warnings,
+    })
+}
+
+/// Fetch a single page of the listing along with its validators, or `None` if `validators` were
+/// given and it didn't change.
+fn fetch_page_from_network(
+    url: &str,
+    marker: &str,
+    validators: &Validators,
+    policy: &RetryPolicy,
+) -> Result<Option<(String, Validators)>> {
+    policy.run(url, || {
+        let mut request = ureq::get(url).set("User-Agent", USER_AGENT);
+        if !marker.is_empty() {
+            request = request.query("marker", marker);
+        }
+        if let Some(etag) = &validators.etag {
+            request = request.set("If-None-Match", etag);
+        }
+        if let Some(last_modified) = &validators.last_modified {
+            request = request.set("If-Modified-Since", last_modified);
+        }
+
+        let response = request.call()?;
+        if response.status() == 304 {
+            return Ok(None);
+        }
+
+        let validators = Validators {
+            etag: response.header("ETag").map(Into::into),
+            last_modified: response.header("Last-Modified").map(Into::into),
+        };
+        Ok(Some((response.into_string()?, validators)))
+    })
+}
+
+/// Splice the `<Blob>` elements of every page into a single listing document.
+///
+/// The header is taken from the first page and the trailer (with its empty `NextMarker`) from the
+/// last, so the result parses the same as a listing that was never paginated.
+fn concatenate_pages(pages: &[String]) -> Result<String> {
+    let mut head = "";
+    let mut tail = "";
+    let mut blobs = String::new();
+
+    for (i, page) in pages.iter().enumerate() {
+        let (page_head, page_blobs, page_tail) = split_blobs(page)
+            .ok_or_else(|| anyhow!("page {} of the listing has no <Blobs> element", i))?;
+
+        if i == 0 {
+            head = page_head;
+        }
+
+        blobs.push_str(page_blobs);
+        tail = page_tail;
+    }
+
+    Ok(format!("{}<Blobs>{}</Blobs>{}", head, blobs, tail))
+}
+
+/// Split a listing page into the text before `<Blobs>`, the contents of it, and the text after it.
+fn split_blobs(page: &str) -> Option<(&str, &str, &str)> {
+    let start = page.find("<Blobs")?;
+    let open_end = start + page[start..].find('>')? + 1;
+
+    // an empty page may be serialized as a self-closing `<Blobs />`
+    if page[..open_end].ends_with("/>") {
+        return Some((&page[..start], "", &page[open_end..]));
+    }
+
+    let close = open_end + page[open_end..].rfind("</Blobs>")?;
+    Some((
+        &page[..start],
+        &page[open_end..close],
+        &page[close + "</Blobs>".len()..],
+    ))
+}
+
+#[test]
+fn concatenated_pages_parse_as_one_listing() {
+    let blob = |name: &str| format!("<Blob><Name>{}</Name></Blob>", name);
+    let pages = vec![
+        format!(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?><EnumerationResults><Blobs>{}{}</Blobs><NextMarker>abc</NextMarker></EnumerationResults>",
+            blob("1.0.0.0/edgedriver_win32.zip"),
+            blob("1.0.0.0/edgedriver_win64.zip")
+        ),
+        "<EnumerationResults><Blobs /><NextMarker>def</NextMarker></EnumerationResults>".into(),
+        format!(
+            "<EnumerationResults><Blobs>{}</Blobs><NextMarker /></EnumerationResults>",
+            blob("2.0.0.0/edgedriver_arm64.zip")
+        ),
+    ];
+
+    let manifest = concatenate_pages(&pages).unwrap();
+    let results: EnumerationResults = from_str(&manifest).unwrap();
+
+    let names: Vec<_> = results
+        .blobs
+        .blobs
+        .iter()
+        .map(|b| b.name.as_str())
+        .collect();
+    assert_eq!(
+        names,
+        [
+            "1.0.0.0/edgedriver_win32.zip",
+            "1.0.0.0/edgedriver_win64.zip",
+            "2.0.0.0/edgedriver_arm64.zip"
+        ]
+    );
+    assert!(results.next_marker.is_empty());
+}
+
+#[cfg(test)]
+fn quick_retries() -> RetryPolicy {
+    RetryPolicy {
+        base_delay: std::time::Duration::from_millis(1),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn fetch_retries_until_upstream_recovers() {
+    use crate::stub::{Reply, StubServer};
+
+    let page = "<EnumerationResults><Blobs><Blob><Name>1.0.0.0/edgedriver_win64.zip</Name></Blob></Blobs><NextMarker /></EnumerationResults>";
+    let server = StubServer::new(vec![
+        Reply::new(503, "unavailable"),
+        Reply::new(502, "bad gateway"),
+        Reply::new(200, page),
+    ]);
+
+    let listing =
+        fetch_manifest_from_network(&server.url("/"), &Validators::default(), &quick_retries())
+            .unwrap()
+            .unwrap();
+
+    assert_eq!(listing.pages, [page]);
+    assert_eq!(listing.results.blobs.blobs.len(), 1);
+    assert_eq!(server.requests().len(), 3);
+}
+
+#[test]
+fn fetch_gives_up_after_max_attempts() {
+    use crate::stub::{Reply, StubServer};
+
+    let server = StubServer::new((0..3).map(|_| Reply::new(503, "unavailable")).collect());
+    let policy = RetryPolicy {
+        max_attempts: 3,
+        ..quick_retries()
+    };
+
+    assert!(
+        fetch_manifest_from_network(&server.url("/"), &Validators::default(), &policy).is_err()
+    );
+    assert_eq!(server.requests().len(), 3);
+}
+
+#[test]
+fn fetch_does_not_retry_client_errors() {
+    use crate::stub::{Reply, StubServer};
+
+    let server = StubServer::new(vec![Reply::new(404, "not found")]);
+
+    assert!(fetch_manifest_from_network(
+        &server.url("/"),
+        &Validators::default(),
+        &quick_retries()
+    )
+    .is_err());
+    assert_eq!(server.requests().len(), 1);
+}
+
+#[test]
+fn fetch_follows_next_marker() {
+    use crate::stub::{Reply, StubServer};
+
+    let server = StubServer::new(vec![
+        Reply::new(200, "<EnumerationResults><Blobs><Blob><Name>a</Name></Blob></Blobs><NextMarker>page2</NextMarker></EnumerationResults>"),
+        Reply::new(200, "<EnumerationResults><Blobs><Blob><Name>b</Name></Blob></Blobs><NextMarker /></EnumerationResults>"),
+    ]);
+
+    let listing =
+        fetch_manifest_from_network(&server.url("/"), &Validators::default(), &quick_retries())
+            .unwrap()
+            .unwrap();
+
+    assert_eq!(listing.pages.len(), 2);
+    assert_eq!(listing.results.blobs.blobs.len(), 2);
+    assert!(server.requests()[1].starts_with("GET /?marker=page2 "));
+}
+
+#[test]
+fn saved_manifest_round_trips() {
+    let listing = Listing {
+        pages: vec![
+            "<EnumerationResults><Blobs><Blob><Name>a</Name></Blob></Blobs><NextMarker>b</NextMarker></EnumerationResults>".into(),
+            "<EnumerationResults><Blobs><Blob><Name>b</Name></Blob></Blobs><NextMarker /></EnumerationResults>".into(),
+        ],
+        results: EnumerationResults::default(),
+        origin: Origin::Stdin,
+        warnings: Vec::new(),
+    };
+
+    let manifest = listing.manifest().unwrap();
+    let reread = read_manifest(manifest.as_bytes(), Origin::Stdin).unwrap();
+
+    assert_eq!(reread.pages, [manifest]);
+    assert_eq!(reread.results.blobs.blobs.len(), 2);
+    assert!(reread.warnings.is_empty());
+
+    let single_page = read_manifest(listing.pages[0].as_bytes(), Origin::Stdin).unwrap();
+    assert_eq!(single_page.warnings.len(), 1);
+}
+
+#[test]
+fn fetch_fails_over_to_mirrors() {
+    use crate::stub::{Reply, StubServer};
+
+    let page = "<EnumerationResults><Blobs><Blob><Name>a</Name></Blob></Blobs><NextMarker /></EnumerationResults>";
+    let primary = StubServer::new(vec![Reply::new(404, "not found")]);
+    let mirror = StubServer::new(vec![Reply::new(200, page)]);
+
+    let urls = vec![primary.url("/"), mirror.url("/manifest.xml")];
+    let listing = Source::Network(urls)
+        .load(&quick_retries(), None)
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(
+        listing.origin,
+        Origin::Network {
+            url: mirror.url("/manifest.xml"),
+            validators: Validators::default(),
+        }
+    );
+    assert_eq!(primary.requests().len(), 1);
+}
+
+#[test]
+fn unchanged_listing_is_not_fetched_again() {
+    use crate::stub::{Reply, StubServer};
+
+    let page = "<EnumerationResults><Blobs><Blob><Name>a</Name></Blob></Blobs><NextMarker /></EnumerationResults>";
+    let server = StubServer::new(vec![
+        Reply::new(200, page)
+            .header("ETag", "\"0x1\"")
+            .header("Last-Modified", "Tue, 15 Mar 2022 00:26:40 GMT"),
+        Reply::new(304, ""),
+    ]);
+    let source = Source::Network(vec![server.url("/")]);
+
+    let listing = source.load(&quick_retries(), None).unwrap().unwrap();
+    let origin: Origin =
+        serde_json::from_value(serde_json::to_value(&listing.origin).unwrap()).unwrap();
+    assert_eq!(origin, listing.origin);
+
+    assert!(source
+        .load(&quick_retries(), Some(&origin))
+        .unwrap()
+        .is_none());
+
+    let requests = server.requests();
+    assert!(!requests[0].contains("If-None-Match"));
+    assert!(requests[1].contains("If-None-Match: \"0x1\"\r\n"));
+    assert!(requests[1].contains("If-Modified-Since: Tue, 15 Mar 2022 00:26:40 GMT\r\n"));
+}
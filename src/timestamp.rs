@@ -0,0 +1,208 @@
+//! Points in time, as parsed from HTTP dates and published as ISO 8601 timestamps.
+
+use std::{
+    fmt,
+    str::FromStr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, Error};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// A point in time with second precision, serialized as an ISO 8601 UTC timestamp.
+///
+/// Parsing also accepts HTTP dates (RFC 1123), like the `Last-Modified` of a blob, so output that
+/// was published before timestamps were normalized can still be read.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timestamp {
+    secs: u64,
+}
+
+impl Timestamp {
+    /// The current time.
+    pub fn now() -> Self {
+        Self::from(SystemTime::now())
+    }
+
+    /// The point in time `duration` before this one, clamped to the unix epoch.
+    pub fn saturating_sub(self, duration: Duration) -> Self {
+        Self {
+            secs: self.secs.saturating_sub(duration.as_secs()),
+        }
+    }
+
+    /// Parse an HTTP date (RFC 1123), like the `Last-Modified` of a blob.
+    pub fn parse_http_date(s: &str) -> Option<Self> {
+        httpdate::parse_http_date(s).ok().map(Self::from)
+    }
+
+    /// Format this point in time as an HTTP date, like the `Last-Modified` of a blob.
+    pub fn to_http_date(self) -> String {
+        httpdate::fmt_http_date(UNIX_EPOCH + Duration::from_secs(self.secs))
+    }
+
+    /// Parse an ISO 8601 UTC timestamp in exactly the form this type is displayed in.
+    pub fn parse_iso8601(s: &str) -> Option<Self> {
+        let field = |range: std::ops::Range<usize>| -> Option<u64> {
+            let digits = s.get(range)?;
+            digits
+                .bytes()
+                .all(|b| b.is_ascii_digit())
+                .then(|| digits.parse().ok())?
+        };
+        let separators = [
+            (4, b'-'),
+            (7, b'-'),
+            (10, b'T'),
+            (13, b':'),
+            (16, b':'),
+            (19, b'Z'),
+        ];
+        if s.len() != 20 || separators.iter().any(|&(i, c)| s.as_bytes()[i] != c) {
+            return None;
+        }
+
+        let days = days_from_civil(
+            field(0..4)? as i64,
+            field(5..7)? as u32,
+            field(8..10)? as u32,
+        );
+        let secs = u64::try_from(days).ok()? * 86_400
+            + field(11..13)? * 3600
+            + field(14..16)? * 60
+            + field(17..19)?;
+        let timestamp = Self { secs };
+
+        // out of range fields (month 13, 25 o'clock, ...) don't survive the round trip
+        (timestamp.to_string() == s).then_some(timestamp)
+    }
+}
+
+impl From<SystemTime> for Timestamp {
+    /// Times before the unix epoch are clamped to it.
+    fn from(time: SystemTime) -> Self {
+        let secs = time
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        Self { secs }
+    }
+}
+
+impl FromStr for Timestamp {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse_iso8601(s)
+            .or_else(|| Self::parse_http_date(s))
+            .ok_or_else(|| anyhow!("invalid timestamp {:?}", s))
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (year, month, day) = civil_from_days((self.secs / 86_400) as i64);
+        let secs = self.secs % 86_400;
+
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+            year,
+            month,
+            day,
+            secs / 3600,
+            secs % 3600 / 60,
+            secs % 60
+        )
+    }
+}
+
+impl Serialize for Timestamp {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Timestamp {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Convert days since the unix epoch into a (year, month, day) date.
+///
+/// See <http://howardhinnant.github.io/date_algorithms.html#civil_from_days>.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    (year, month, day)
+}
+
+/// Convert a (year, month, day) date into days since the unix epoch.
+///
+/// See <http://howardhinnant.github.io/date_algorithms.html#days_from_civil>.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = year - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = i64::from((month + 9) % 12);
+    let doy = (153 * mp + 2) / 5 + i64::from(day) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+
+    era * 146_097 + doe - 719_468
+}
+
+#[test]
+fn iso8601() {
+    let at = |secs| Timestamp { secs }.to_string();
+
+    assert_eq!(at(0), "1970-01-01T00:00:00Z");
+    assert_eq!(at(951_782_400), "2000-02-29T00:00:00Z");
+    assert_eq!(at(1_647_304_000), "2022-03-15T00:26:40Z");
+
+    let last_modified = Timestamp::parse_http_date("Tue, 15 Mar 2022 00:26:40 GMT").unwrap();
+    assert_eq!(last_modified.to_string(), "2022-03-15T00:26:40Z");
+    assert_eq!(
+        last_modified.to_http_date(),
+        "Tue, 15 Mar 2022 00:26:40 GMT"
+    );
+}
+
+#[test]
+fn parse_accepts_iso8601_and_http_dates() {
+    for s in [
+        "1970-01-01T00:00:00Z",
+        "2000-02-29T00:00:00Z",
+        "2022-03-15T00:26:40Z",
+    ] {
+        assert_eq!(s.parse::<Timestamp>().unwrap().to_string(), s);
+    }
+    assert_eq!(
+        "Tue, 15 Mar 2022 00:26:40 GMT"
+            .parse::<Timestamp>()
+            .unwrap()
+            .to_string(),
+        "2022-03-15T00:26:40Z"
+    );
+
+    for s in [
+        "",
+        "2022-13-01T00:00:00Z",
+        "2022-02-30T00:00:00Z",
+        "2022-03-15T24:00:00Z",
+        "2022-03-15 00:26:40Z",
+        "2022-03-15T00:26:40",
+        "+022-03-15T00:26:40Z",
+    ] {
+        assert!(s.parse::<Timestamp>().is_err(), "{:?}", s);
+    }
+}
@@ -0,0 +1,300 @@
+//! Downloading every driver archive to check it against its listed size and `Content-MD5`.
+
+use std::{
+    fs::{create_dir_all, read_to_string, remove_file, rename, File},
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    process,
+};
+
+use anyhow::Result;
+use md5::Md5;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256, Sha512};
+
+use crate::{properties::Hex, retry::RetryPolicy, write_json, Output, Properties, USER_AGENT};
+
+/// The outcome of verifying a single driver archive, as published in `verification`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Verification {
+    /// The archive matches every property that was listed for it.
+    Verified,
+    /// The archive doesn't have the listed `Content-Length`.
+    SizeMismatch,
+    /// The archive doesn't have the listed `Content-MD5`.
+    Md5Mismatch,
+    /// The archive couldn't be downloaded.
+    DownloadFailed,
+}
+
+/// What is known about a downloaded archive, as stored in the cache.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Digests {
+    /// The size of the archive, in bytes.
+    pub size: u64,
+    /// The MD5 of the archive, to compare with the listed `Content-MD5`.
+    pub md5: Hex,
+    /// The SHA-256 of the archive, which it is stored under.
+    pub sha256: Hex,
+    /// The SHA-512 of the archive.
+    pub sha512: Hex,
+}
+
+/// A local, content-addressed store of downloaded archives.
+///
+/// Archives are stored as `objects/<sha256>`, and `etags/<key>.json` maps a URL and `ETag` to the
+/// digests of its content, so a blob that didn't change upstream is never downloaded twice.
+pub struct Cache {
+    root: PathBuf,
+}
+
+impl Cache {
+    /// The cache in the `root` directory.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn key(url: &str, etag: &str) -> String {
+        hex::encode(
+            Sha256::new()
+                .chain_update(url)
+                .chain_update("\n")
+                .chain_update(etag)
+                .finalize(),
+        )
+    }
+
+    fn entry(&self, url: &str, etag: &str) -> PathBuf {
+        self.root
+            .join("etags")
+            .join(format!("{}.json", Self::key(url, etag)))
+    }
+
+    fn object(&self, sha256: &Hex) -> PathBuf {
+        self.root.join("objects").join(sha256.to_string())
+    }
+
+    /// The digests of a previously downloaded blob, if its archive is still in the cache.
+    fn get(&self, url: &str, etag: &str) -> Option<Digests> {
+        let content = read_to_string(self.entry(url, etag)).ok()?;
+        let digests: Digests = serde_json::from_str(&content).ok()?;
+        self.object(&digests.sha256).exists().then_some(digests)
+    }
+
+    /// Download `url` into the cache.
+    fn download(&self, url: &str, etag: &str, policy: &RetryPolicy) -> Result<Digests> {
+        let objects = self.root.join("objects");
+        create_dir_all(&objects)?;
+        create_dir_all(self.root.join("etags"))?;
+
+        // unique per blob and process, so concurrent downloads never write to the same file
+        let partial = objects.join(format!(
+            ".{}.{}.partial",
+            Self::key(url, etag),
+            process::id()
+        ));
+        let digests = match download(url, &partial, policy) {
+            Ok(digests) => digests,
+            Err(e) => {
+                let _ = remove_file(&partial);
+                return Err(e);
+            }
+        };
+
+        rename(&partial, self.object(&digests.sha256))?;
+        write_json(&self.entry(url, etag), &digests)?;
+        Ok(digests)
+    }
+}
+
+/// Download `url` to `path`, computing its digests along the way.
+pub fn download(url: &str, path: &Path, policy: &RetryPolicy) -> Result<Digests> {
+    policy.run(url, || {
+        let mut reader = ureq::get(url)
+            .set("User-Agent", USER_AGENT)
+            .call()?
+            .into_reader();
+        let mut file = File::create(path)?;
+
+        let (mut md5, mut sha256, mut sha512) = (Md5::new(), Sha256::new(), Sha512::new());
+        let mut size = 0;
+        let mut buffer = vec![0; 64 * 1024];
+        loop {
+            let read = reader.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+
+            let chunk = &buffer[..read];
+            md5.update(chunk);
+            sha256.update(chunk);
+            sha512.update(chunk);
+            file.write_all(chunk)?;
+            size += read as u64;
+        }
+
+        Ok(Digests {
+            size,
+            md5: Hex(md5.finalize().to_vec()),
+            sha256: Hex(sha256.finalize().to_vec()),
+            sha512: Hex(sha512.finalize().to_vec()),
+        })
+    })
+}
+
+/// Verify every driver in `output` that can still be downloaded, and record the outcome (and,
+/// for verified drivers, their SHA-256 and SHA-512) in its properties.
+///
+/// Returns a description of each driver that failed verification, including why downloads failed.
+pub fn verify(output: &mut Output, cache: &Cache, policy: &RetryPolicy) -> Vec<String> {
+    let mut failed = Vec::new();
+    for (version, platforms) in &mut output.0 {
+        for (platform, properties) in platforms {
+            if properties.removed_upstream_at.is_some() {
+                continue;
+            }
+
+            let digests = match cache.get(&properties.url, &properties.etag) {
+                Some(digests) => Ok(digests),
+                None => cache.download(&properties.url, &properties.etag, policy),
+            };
+            let (verification, reason) = match digests {
+                Ok(digests) => {
+                    let verification = check(properties, digests);
+                    (verification, format!("{:?}", verification))
+                }
+                Err(e) => (
+                    Verification::DownloadFailed,
+                    format!("DownloadFailed ({}: {})", properties.url, e),
+                ),
+            };
+
+            if verification != Verification::Verified {
+                failed.push(format!("{} {}: {}", version, platform, reason));
+            }
+            properties.verification = Some(verification);
+        }
+    }
+
+    failed
+}
+
+/// Compare the digests of a download with the listed properties, keeping the SHA-2 digests when
+/// they match.
+fn check(properties: &mut Properties, digests: Digests) -> Verification {
+    if properties
+        .content_length
+        .is_some_and(|length| length != digests.size)
+    {
+        return Verification::SizeMismatch;
+    }
+    if properties
+        .md5_digest()
+        .is_some_and(|md5| md5 != digests.md5.0)
+    {
+        return Verification::Md5Mismatch;
+    }
+
+    properties.sha256 = Some(digests.sha256);
+    properties.sha512 = Some(digests.sha512);
+    Verification::Verified
+}
+
+#[cfg(test)]
+fn driver(url: String, body: &[u8], etag: &str) -> Output {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use std::collections::BTreeMap;
+
+    let mut malformed = Vec::new();
+    let properties = Properties::from_blob(
+        crate::Blob {
+            name: "100.0.1154.0/edgedriver_win64.zip".into(),
+            url,
+            properties: crate::BlobProperties {
+                etag: etag.into(),
+                content_length: body.len().to_string(),
+                content_md5: STANDARD.encode(Md5::digest(body)),
+                ..Default::default()
+            },
+        },
+        false,
+        &mut malformed,
+    );
+
+    Output(BTreeMap::from([(
+        "100.0.1154.0".parse().unwrap(),
+        BTreeMap::from([(crate::Platform::Win64, properties)]),
+    )]))
+}
+
+#[test]
+fn verified_downloads_are_cached_by_etag() {
+    use crate::stub::{Reply, StubServer};
+
+    let tmp = tempfile::tempdir().unwrap();
+    let cache = Cache::new(tmp.path());
+    let server = StubServer::new(vec![Reply::new(200, "driver")]);
+    let policy = RetryPolicy {
+        max_attempts: 1,
+        ..Default::default()
+    };
+
+    for _ in 0..2 {
+        let mut output = driver(server.url("/win64.zip"), b"driver", "0x1");
+        assert!(verify(&mut output, &cache, &policy).is_empty());
+
+        let properties = output.0.values().next().unwrap().values().next().unwrap();
+        assert_eq!(properties.verification, Some(Verification::Verified));
+        assert_eq!(
+            properties.sha256.as_ref().unwrap().to_string(),
+            "b4def8217cadae26d4da633fd2a4e58e326cbb5d570afdc3989484da07af3579"
+        );
+    }
+    assert_eq!(server.requests().len(), 1);
+
+    // only the archive is left in objects, no partial download
+    let objects: Vec<_> = std::fs::read_dir(tmp.path().join("objects"))
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    assert_eq!(
+        objects,
+        ["b4def8217cadae26d4da633fd2a4e58e326cbb5d570afdc3989484da07af3579"]
+    );
+
+    // a failed download doesn't leave its partial file behind either
+    let mut output = driver(server.url("/gone.zip"), b"driver", "0x2");
+    assert_eq!(verify(&mut output, &cache, &policy).len(), 1);
+    assert_eq!(
+        std::fs::read_dir(tmp.path().join("objects"))
+            .unwrap()
+            .count(),
+        1
+    );
+}
+
+#[test]
+fn mismatching_downloads_fail_verification() {
+    use crate::stub::{Reply, StubServer};
+
+    let tmp = tempfile::tempdir().unwrap();
+    let server = StubServer::new(vec![
+        Reply::new(200, "tampered"),
+        Reply::new(200, "drivers"),
+    ]);
+    let policy = RetryPolicy {
+        max_attempts: 1,
+        ..Default::default()
+    };
+
+    let mut output = driver(server.url("/a.zip"), b"original", "0x1");
+    let failed = verify(&mut output, &Cache::new(tmp.path()), &policy);
+    assert_eq!(failed, ["100.0.1154.0 win64: Md5Mismatch"]);
+
+    let mut output = driver(server.url("/b.zip"), b"driver", "0x1");
+    verify(&mut output, &Cache::new(tmp.path()), &policy);
+    let properties = output.0.values().next().unwrap().values().next().unwrap();
+    assert_eq!(properties.verification, Some(Verification::SizeMismatch));
+    assert_eq!(properties.sha256, None);
+}
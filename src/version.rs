@@ -0,0 +1,127 @@
+//! Four-part driver versions, like `100.0.1154.0`.
+
+use std::{fmt, str::FromStr};
+
+use anyhow::{anyhow, ensure, Error};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// A four-part msedgedriver version, e.g. `100.0.1154.0`.
+///
+/// Fields are compared in declaration order, so versions sort numerically rather than as text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Version {
+    /// The major release, the same as the browser's.
+    pub major: u32,
+    /// Always `0` so far.
+    pub minor: u32,
+    /// The build number, e.g. `1154`.
+    pub build: u32,
+    /// The patch number of the build.
+    pub patch: u32,
+}
+
+impl FromStr for Version {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts = s
+            .split('.')
+            .map(|part| {
+                ensure!(
+                    !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit()),
+                    "invalid version component {:?}",
+                    part
+                );
+                Ok(part.parse()?)
+            })
+            .collect::<Result<Vec<u32>, Error>>()
+            .map_err(|e| anyhow!("invalid version {:?}: {}", s, e))?;
+
+        match parts[..] {
+            [major, minor, build, patch] => Ok(Self {
+                major,
+                minor,
+                build,
+                patch,
+            }),
+            _ => Err(anyhow!(
+                "invalid version {:?}: expected 4 components, found {}",
+                s,
+                parts.len()
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}.{}.{}.{}",
+            self.major, self.minor, self.build, self.patch
+        )
+    }
+}
+
+impl Serialize for Version {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Version {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[test]
+fn versions_sort_numerically() {
+    let mut versions: Vec<Version> = [
+        "100.0.1154.0",
+        "99.0.1150.2",
+        "100.0.999.10",
+        "99.0.1150.10",
+    ]
+    .iter()
+    .map(|v| v.parse().unwrap())
+    .collect();
+    versions.sort();
+
+    let sorted: Vec<_> = versions.iter().map(ToString::to_string).collect();
+    assert_eq!(
+        sorted,
+        [
+            "99.0.1150.2",
+            "99.0.1150.10",
+            "100.0.999.10",
+            "100.0.1154.0"
+        ]
+    );
+}
+
+#[test]
+fn invalid_versions_are_rejected() {
+    for invalid in [
+        "",
+        "100",
+        "100.0.1154",
+        "100.0.1154.0.1",
+        "100.0.x.0",
+        "1.-2.3.4",
+        "LATEST_STABLE",
+    ] {
+        assert!(invalid.parse::<Version>().is_err(), "{:?} parsed", invalid);
+    }
+}
+
+#[test]
+fn version_serde_round_trip() {
+    let version: Version = serde_json::from_str(r#""118.0.2088.76""#).unwrap();
+    assert_eq!(version.major, 118);
+    assert_eq!(
+        serde_json::to_string(&version).unwrap(),
+        r#""118.0.2088.76""#
+    );
+}
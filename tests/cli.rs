@@ -0,0 +1,449 @@
+//! Runs each command of the binary against the manifests in `tests/fixtures`.
+
+use std::{
+    fs::{create_dir_all, read_to_string, remove_file, write},
+    io::{BufRead, BufReader},
+    path::Path,
+    process::{Child, Command, Output, Stdio},
+};
+
+use tempfile::TempDir;
+
+const BIN: &str = env!("CARGO_BIN_EXE_msedgedriver-manifest-cache");
+
+/// The drivers listed in `tests/fixtures/manifest.xml`.
+const DRIVERS: [&str; 3] = [
+    "100.0.1154.0/edgedriver_linux64.zip",
+    "100.0.1154.0/edgedriver_win64.zip",
+    "101.0.1160.0/edgedriver_win64.zip",
+];
+
+fn command(dir: &Path, args: &[&str]) -> Command {
+    let mut command = Command::new(BIN);
+    command
+        .args(args)
+        .current_dir(dir)
+        .env_remove("MSEDGEDRIVER_MANIFEST_URL")
+        .env_remove("MSEDGEDRIVER_MANIFEST_MIRRORS")
+        .env_remove("MSEDGEDRIVER_MANIFEST_CONFIG");
+    command
+}
+
+fn run(dir: &Path, args: &[&str]) -> Output {
+    command(dir, args).output().unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+/// Write a fixture manifest into `dir`, with its driver URLs pointing at `base`.
+fn fixture(dir: &Path, name: &str, base: &str) {
+    let manifest = read_to_string(
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(name),
+    )
+    .unwrap();
+    write(dir.join(name), manifest.replace("{base}", base)).unwrap();
+}
+
+/// A running `serve` command, killed when dropped.
+struct Server {
+    child: Child,
+    base: String,
+}
+
+impl Server {
+    /// Serve `root`, passing `args` on to `serve`.
+    fn start(dir: &Path, root: &str, args: &[&str]) -> Self {
+        let mut child = command(dir, &["serve", "--out", root, "--addr", "127.0.0.1:0"])
+            .args(args)
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+
+        let mut line = String::new();
+        BufReader::new(child.stdout.as_mut().unwrap())
+            .read_line(&mut line)
+            .unwrap();
+        let base = line.trim().rsplit(' ').next().unwrap().to_owned();
+        assert!(base.starts_with("http://"), "unexpected output {:?}", line);
+
+        Self { child, base }
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Serve a temporary directory holding the driver archives under `files` and a `dist` built from
+/// `tests/fixtures/manifest.xml` that points at them.
+fn published() -> (TempDir, Server) {
+    let tmp = tempfile::tempdir().unwrap();
+    for driver in DRIVERS {
+        let path = tmp.path().join("files").join(driver);
+        create_dir_all(path.parent().unwrap()).unwrap();
+        write(path, "driver").unwrap();
+    }
+
+    let server = Server::start(tmp.path(), ".", &[]);
+    fixture(
+        tmp.path(),
+        "manifest.xml",
+        &format!("{}/files", server.base),
+    );
+    let output = run(tmp.path(), &["build", "--source", "manifest.xml"]);
+    assert!(output.status.success(), "{:?}", output);
+
+    (tmp, server)
+}
+
+#[test]
+fn build() {
+    let tmp = tempfile::tempdir().unwrap();
+    fixture(tmp.path(), "manifest.xml", "https://example.com");
+
+    let output = run(
+        tmp.path(),
+        &["build", "--source", "manifest.xml", "--out", "out"],
+    );
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(stdout(&output), "published out\n");
+
+    let index = read_to_string(tmp.path().join("out").join("index.json")).unwrap();
+    let index: serde_json::Value = serde_json::from_str(&index).unwrap();
+    assert_eq!(index["versions"][0]["version"], "100.0.1154.0");
+    assert_eq!(index["versions"][1]["version"], "101.0.1160.0");
+
+    // without a command, the options are build options
+    let output = run(
+        tmp.path(),
+        &[
+            "--from-file",
+            "manifest.xml",
+            "--strictness",
+            "deny",
+            "--quiet",
+        ],
+    );
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stdout(&output), "");
+
+    // warnings are still printed, only the status messages are left out
+    let output = run(
+        tmp.path(),
+        &[
+            "build",
+            "--source",
+            "manifest.xml",
+            "--out",
+            "out",
+            "--quiet",
+        ],
+    );
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(stdout(&output), "");
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("warning: skipped blob 100.0.1154.0/credits.html"));
+
+    let output = run(tmp.path(), &["build", "--bogus"]);
+    assert_eq!(output.status.code(), Some(2));
+    let output = run(tmp.path(), &["build", "--format", "json"]);
+    assert_eq!(output.status.code(), Some(2));
+    let output = run(tmp.path(), &["build", "--retries", "many"]);
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn fetch() {
+    let tmp = tempfile::tempdir().unwrap();
+    fixture(tmp.path(), "manifest.xml", "https://example.com");
+
+    let output = run(tmp.path(), &["fetch", "--source", "manifest.xml"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        stdout(&output),
+        read_to_string(tmp.path().join("manifest.xml")).unwrap()
+    );
+
+    // nothing listens on port 1, so this fails on the first attempt
+    let output = run(
+        tmp.path(),
+        &["fetch", "--url", "http://127.0.0.1:1", "--retries", "0"],
+    );
+    assert_eq!(output.status.code(), Some(1));
+    assert!(!String::from_utf8_lossy(&output.stderr).contains("retrying"));
+}
+
+#[test]
+fn verify() {
+    let (tmp, _server) = published();
+
+    let output = run(tmp.path(), &["verify", "--verify-cache", "cache"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(stdout(&output), "0 of 3 drivers failed verification\n");
+
+    write(tmp.path().join("files").join(DRIVERS[0]), "tamper").unwrap();
+    let output = run(
+        tmp.path(),
+        &["verify", "--verify-cache", "other", "--format", "json"],
+    );
+    assert_eq!(output.status.code(), Some(4));
+    let report: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(
+        report,
+        serde_json::json!({
+            "checked": 3,
+            "failed": ["100.0.1154.0 linux64: Md5Mismatch"],
+        })
+    );
+}
+
+#[test]
+fn diff() {
+    let tmp = tempfile::tempdir().unwrap();
+    fixture(tmp.path(), "manifest-old.xml", "https://example.com");
+    fixture(tmp.path(), "manifest.xml", "https://example.com");
+
+    let output = run(tmp.path(), &["diff", "manifest-old.xml", "manifest.xml"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        stdout(&output),
+        "+ 101.0.1160.0 (win64)\n\
+         - 99.0.1150.2 (win64)\n\
+         + 100.0.1154.0 drivers: linux64\n\
+         new platforms: linux64\n"
+    );
+    assert!(tmp.path().join("changes.json").exists());
+
+    let output = run(
+        tmp.path(),
+        &[
+            "--format",
+            "json",
+            "diff",
+            "manifest.xml",
+            "manifest.xml",
+            "--json",
+            "same.json",
+        ],
+    );
+    let changes: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(changes["added"], serde_json::json!([]));
+}
+
+#[test]
+fn query() {
+    let (tmp, server) = published();
+    let dist = format!("{}/dist", server.base);
+
+    let output = run(
+        tmp.path(),
+        &["query", "100", "--platform", "linux64", "--source", &dist],
+    );
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        stdout(&output),
+        format!(
+            "100.0.1154.0 linux64 {}/files/100.0.1154.0/edgedriver_linux64.zip\n",
+            server.base
+        )
+    );
+
+    let output = run(
+        tmp.path(),
+        &[
+            "query",
+            "101.0.1160.0",
+            "--platform",
+            "win64",
+            "--format",
+            "json",
+        ],
+    );
+    assert!(output.status.success(), "{:?}", output);
+    let driver: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(driver["version"], "101.0.1160.0");
+    assert_eq!(driver["contentLength"], 6);
+
+    let output = run(tmp.path(), &["query", "101", "--platform", "linux64"]);
+    assert_eq!(output.status.code(), Some(1));
+
+    let output = run(
+        tmp.path(),
+        &[
+            "query",
+            "--browser",
+            "Microsoft Edge 100.0.1154.3 unknown",
+            "--platform",
+            "win64",
+        ],
+    );
+    assert!(output.status.success(), "{:?}", output);
+    assert!(stdout(&output).starts_with("100.0.1154.0 win64 "));
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        let edge = tmp.path().join("microsoft-edge-beta");
+        write(&edge, "#!/bin/sh\necho Microsoft Edge 101.0.1160.5 beta\n").unwrap();
+        std::fs::set_permissions(&edge, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let output = run(
+            tmp.path(),
+            &[
+                "query",
+                "installed",
+                "--edge-command",
+                edge.to_str().unwrap(),
+                "--platform",
+                "win64",
+            ],
+        );
+        assert!(output.status.success(), "{:?}", output);
+        assert!(stdout(&output).starts_with("101.0.1160.0 win64 "));
+    }
+
+    let output = run(tmp.path(), &["query", "100", "--edge-command", "edge"]);
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn install() {
+    use std::io::Write;
+    use zip::{write::FileOptions, ZipWriter};
+
+    let tmp = tempfile::tempdir().unwrap();
+    let mut zip = ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    zip.start_file("msedgedriver", FileOptions::default())
+        .unwrap();
+    zip.write_all(b"#!/bin/sh\n").unwrap();
+    let archive = zip.finish().unwrap().into_inner();
+
+    let path = tmp.path().join("files").join(DRIVERS[0]);
+    create_dir_all(path.parent().unwrap()).unwrap();
+    write(path, &archive).unwrap();
+    let server = Server::start(tmp.path(), ".", &[]);
+
+    create_dir_all(tmp.path().join("dist").join("versions")).unwrap();
+    write(
+        tmp.path().join("dist/versions/100.0.1154.0.json"),
+        serde_json::json!({
+            "linux64": {
+                "url": format!("{}/files/{}", server.base, DRIVERS[0]),
+                "etag": "0x1",
+                "contentLength": archive.len(),
+            }
+        })
+        .to_string(),
+    )
+    .unwrap();
+
+    let installed = Path::new("drivers")
+        .join("100.0.1154.0")
+        .join("linux64")
+        .join("0x1")
+        .join("msedgedriver");
+    for _ in 0..2 {
+        let output = run(
+            tmp.path(),
+            &[
+                "install",
+                "100.0.1154.0",
+                "--platform",
+                "linux64",
+                "--dir",
+                "drivers",
+            ],
+        );
+        assert!(output.status.success(), "{:?}", output);
+        assert_eq!(
+            stdout(&output),
+            format!("100.0.1154.0 linux64 {}\n", installed.display())
+        );
+    }
+    assert_eq!(
+        read_to_string(tmp.path().join(&installed)).unwrap(),
+        "#!/bin/sh\n"
+    );
+
+    let output = run(tmp.path(), &["cache", "list", "--dir", "drivers"]);
+    assert!(stdout(&output).ends_with("cached 1 drivers, 10 bytes in drivers\n"));
+}
+
+#[test]
+fn serve() {
+    let (tmp, server) = published();
+
+    let response = ureq::get(&format!("{}/dist/versions/100.0.1154.0.json", server.base))
+        .call()
+        .unwrap();
+    assert_eq!(response.content_type(), "application/json");
+    let versions: serde_json::Value =
+        serde_json::from_str(&response.into_string().unwrap()).unwrap();
+    assert_eq!(versions["win64"]["contentLength"], 6);
+
+    // the address is the output of serve, so --quiet keeps it
+    let quiet = Server::start(tmp.path(), "dist", &["--quiet"]);
+    let response = ureq::get(&format!("{}/index.json", quiet.base))
+        .call()
+        .unwrap();
+    assert_eq!(response.status(), 200);
+}
+
+#[test]
+fn check_links() {
+    let (tmp, _server) = published();
+
+    let output = run(tmp.path(), &["check-links"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        stdout(&output),
+        format!(
+            "0 of 3 links drifted, see {}\n",
+            Path::new("dist").join("link-health.json").display()
+        )
+    );
+
+    remove_file(tmp.path().join("files").join(DRIVERS[2])).unwrap();
+    let output = run(tmp.path(), &["check-links", "--format", "json"]);
+    assert_eq!(output.status.code(), Some(4));
+    let summary: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(summary, serde_json::json!({ "checked": 3, "drifted": 1 }));
+}
+
+#[test]
+fn cache() {
+    let tmp = tempfile::tempdir().unwrap();
+
+    let output = run(tmp.path(), &["cache", "list", "--dir", "drivers"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(stdout(&output), "cached 0 drivers, 0 bytes in drivers\n");
+
+    let output = run(
+        tmp.path(),
+        &[
+            "cache", "prune", "--dir", "drivers", "--keep", "1", "--format", "json",
+        ],
+    );
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(stdout(&output), "[]\n");
+
+    let output = run(tmp.path(), &["cache", "prune", "--dir", "drivers"]);
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn help() {
+    let output = run(Path::new("."), &["--help"]);
+    assert!(output.status.success());
+    assert!(stdout(&output).starts_with("usage: msedgedriver-manifest-cache"));
+
+    let output = run(Path::new("."), &["bogus"]);
+    assert_eq!(output.status.code(), Some(2));
+}